@@ -0,0 +1,95 @@
+use std::{
+    env,
+    ffi::OsStr,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::LitStr;
+
+pub fn embed_migrations(directory: &LitStr) -> TokenStream {
+    match read_migration_files(directory) {
+        Ok(files) => {
+            let migrations = files.iter().map(|(name, path)| {
+                quote! {
+                    ts_sql_helper_lib::EmbeddedMigration {
+                        name: #name,
+                        sql: include_str!(#path),
+                    }
+                }
+            });
+
+            quote! {
+                &[ #( #migrations ),* ]
+            }
+        }
+        Err(error) => error.to_compile_error(),
+    }
+}
+
+/// Reads the name ordered `(file name, absolute path)` pairs of the `*.sql` files in the directory
+/// and its subdirectories, relative paths are resolved against the crate's manifest directory.
+fn read_migration_files(directory: &LitStr) -> syn::Result<Vec<(String, String)>> {
+    let manifest_directory =
+        env::var("CARGO_MANIFEST_DIR").map_err(|error| syn::Error::new(directory.span(), error))?;
+    let path = PathBuf::from(manifest_directory).join(directory.value());
+
+    let mut paths = Vec::new();
+    collect_migration_files(directory, &path, &mut paths)?;
+
+    let mut files = Vec::new();
+    for path in paths {
+        let Some(path_string) = path.to_str() else {
+            return Err(syn::Error::new(
+                directory.span(),
+                format!("migration path `{}` is not valid UTF-8", path.display()),
+            ));
+        };
+        let name = path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+
+        files.push((name, path_string.to_string()));
+    }
+    files.sort();
+
+    Ok(files)
+}
+
+/// Collects the paths of the `*.sql` files in the directory and its subdirectories.
+fn collect_migration_files(
+    literal: &LitStr,
+    directory: &Path,
+    paths: &mut Vec<PathBuf>,
+) -> syn::Result<()> {
+    let entries = fs::read_dir(directory).map_err(|error| {
+        syn::Error::new(
+            literal.span(),
+            format!(
+                "could not read migration directory `{}`: {error}",
+                directory.display()
+            ),
+        )
+    })?;
+
+    for entry in entries {
+        let path = entry
+            .map_err(|error| syn::Error::new(literal.span(), error))?
+            .path();
+
+        if path.is_dir() {
+            collect_migration_files(literal, &path, paths)?;
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == OsStr::new("sql"))
+        {
+            paths.push(path);
+        }
+    }
+
+    Ok(())
+}
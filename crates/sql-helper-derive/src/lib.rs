@@ -7,8 +7,8 @@ use proc_macro::TokenStream;
 use quote::{quote, quote_spanned};
 use regex::Regex;
 use syn::{
-    Data, DeriveInput, Fields, GenericParam, Generics, Type, TypeParamBound, parse_macro_input,
-    parse_quote, spanned::Spanned,
+    Data, DeriveInput, Fields, GenericParam, Generics, LitStr, Type, TypeParamBound,
+    parse_macro_input, parse_quote, spanned::Spanned,
 };
 
 use crate::query::{
@@ -19,9 +19,32 @@ use crate::query::{
     test::create_test,
 };
 
+mod embed_migrations;
 mod query;
 
+/// Macro for embedding the `*.sql` files of a migrations directory and its subdirectories into the
+/// binary, the path is relative to the crate's manifest directory.
+///
+/// Expands to a name ordered `&[ts_sql_helper_lib::EmbeddedMigration]`. Files that are added to
+/// the directory are only picked up once the invoking crate is rebuilt.
+#[proc_macro]
+pub fn embed_migrations(input: TokenStream) -> TokenStream {
+    let directory = parse_macro_input!(input as LitStr);
+
+    embed_migrations::embed_migrations(&directory).into()
+}
+
 /// Macro for creating and test SQL.
+///
+/// With the `verify` feature, the query is also prepared against the database in `DATABASE_URL`
+/// at compile time, and rejected queries fail to compile. Without `DATABASE_URL`, or with
+/// `SQL_HELPER_OFFLINE=1`, the query is instead checked against the metadata cached in
+/// `.sql-helper/` by `sql-helper prepare`.
+///
+/// If the `row` fields are omitted from a verified query that returns columns, the fields of the
+/// row struct are inferred from the column names and types. Columns read directly from a table
+/// with a `NOT NULL` constraint are not optional, so nullable sides of outer joins need their
+/// `row` fields written by hand.
 #[proc_macro]
 pub fn query(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as QueryMacroInput);
@@ -31,6 +54,12 @@ pub fn query(input: TokenStream) -> TokenStream {
         LazyLock::new(|| Regex::new(r"(?m)(\r\n|\r|\n| ){2,}").unwrap());
     let query = REGEX.replace_all(query.trim(), " ");
 
+    #[cfg(feature = "verify")]
+    let verified = match query::verify::verify_query(&input.name, &input.query, &query) {
+        Ok(verified) => verified,
+        Err(error) => return error.to_compile_error().into(),
+    };
+
     let parameters: Vec<Type> = get_param_types(&query)
         .into_iter()
         .enumerate()
@@ -50,9 +79,35 @@ pub fn query(input: TokenStream) -> TokenStream {
 
     let struct_name = input.name;
 
-    let main_struct = create_main_struct(&struct_name, &query, &parameters);
-    let test = create_test(&struct_name);
-    let row_struct = if let Some(row_fields) = input.row {
+    // Including the cached metadata makes Cargo expand the query again when it is re-prepared.
+    #[cfg(feature = "verify")]
+    let track_cache = verified
+        .as_ref()
+        .and_then(|verified| verified.cache_path.as_ref())
+        .map(|path| {
+            let path = path.to_string_lossy();
+            quote! { const _: &[u8] = include_bytes!(#path); }
+        });
+    #[cfg(not(feature = "verify"))]
+    let track_cache = proc_macro2::TokenStream::new();
+
+    #[cfg(feature = "verify")]
+    let row = match (input.row, verified.map(|verified| verified.metadata)) {
+        (Some(row_fields), _) => Some(row_fields),
+        (None, Some(metadata)) if !metadata.columns.is_empty() => {
+            match query::row_struct::infer_row_fields(&metadata, &input.query) {
+                Ok(row_fields) => Some(row_fields),
+                Err(error) => return error.to_compile_error().into(),
+            }
+        }
+        (None, _) => None,
+    };
+    #[cfg(not(feature = "verify"))]
+    let row = input.row;
+
+    let main_struct = create_main_struct(&struct_name, &query, &parameters, row.is_some());
+    let test = create_test(&struct_name, row.as_deref());
+    let row_struct = if let Some(row_fields) = row {
         create_row_struct(&struct_name, &row_fields)
     } else {
         proc_macro2::TokenStream::new()
@@ -62,6 +117,7 @@ pub fn query(input: TokenStream) -> TokenStream {
         #main_struct
         #row_struct
         #test
+        #track_cache
     }
     .into()
 }
@@ -73,6 +129,7 @@ pub fn derive_from_row(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
 
     let name = input.ident;
+    let name_lit = name.to_string();
 
     // Add required trait bounds depending on type.
     let generics = add_trait_bounds(
@@ -91,11 +148,13 @@ pub fn derive_from_row(input: TokenStream) -> TokenStream {
 
     let each_field_from_row = fields.named.iter().filter_map(|f| {
         let name = f.ident.as_ref()?;
-        let name_lit = name.to_string();
+        let field_lit = name.to_string();
         let field_type = &f.ty;
 
         Some(quote_spanned! {f.span()=>
-            let #name: #field_type = row.try_get(#name_lit)?;
+            let #name: #field_type = row.try_get(#field_lit).inspect_err(|error| {
+                ts_sql_helper_lib::instrument::from_row_failed(#name_lit, #field_lit, error)
+            })?;
         })
     });
 
@@ -0,0 +1,113 @@
+//! The checked-in query metadata that queries are verified against when there is no database.
+//!
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use syn::Ident;
+
+/// The name of the directory containing the cached metadata, searched for in the crate's manifest
+/// directory and its ancestors.
+pub const CACHE_DIRECTORY: &str = ".sql-helper";
+
+/// The environment variable set by `sql-helper prepare` to the cache directory the metadata of
+/// each verified query should be written to.
+pub const PREPARE_DIRECTORY: &str = "SQL_HELPER_PREPARE";
+
+/// The number of characters of the query's hash that are included in the path of its metadata.
+const PATH_HASH_LENGTH: usize = 16;
+
+/// The metadata of a prepared query.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QueryMetadata {
+    /// The hash of the query the metadata was prepared from.
+    pub hash: String,
+    /// The query the metadata was prepared from.
+    pub query: String,
+    /// The type names of the parameters.
+    pub parameters: Vec<String>,
+    /// The columns returned by the query.
+    pub columns: Vec<ColumnMetadata>,
+}
+
+/// The metadata of a column returned by a prepared query.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ColumnMetadata {
+    /// The name of the column.
+    pub name: String,
+    /// The type name of the column.
+    pub r#type: String,
+    /// If the column may be null, only columns read directly from a table with a `NOT NULL`
+    /// constraint are not nullable.
+    pub nullable: bool,
+}
+
+/// Calculates the hash of a query's text.
+pub fn hash(query: &str) -> String {
+    Sha256::digest(query.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Finds the cache directory in the crate's manifest directory or its ancestors.
+pub fn find_directory() -> Option<PathBuf> {
+    let manifest_directory = PathBuf::from(env::var_os("CARGO_MANIFEST_DIR")?);
+
+    manifest_directory
+        .ancestors()
+        .map(|directory| directory.join(CACHE_DIRECTORY))
+        .find(|directory| directory.is_dir())
+}
+
+/// The path of the query's metadata in the cache directory, `<package>/<name>-<hash>.json`.
+///
+/// The path includes the start of the query's hash, as queries in different modules or targets of
+/// a package may share a name.
+pub fn metadata_path(directory: &Path, name: &Ident, hash: &str) -> PathBuf {
+    let package = env::var("CARGO_PKG_NAME").unwrap_or_default();
+    let hash = hash.get(..PATH_HASH_LENGTH).unwrap_or(hash);
+
+    directory.join(package).join(format!("{name}-{hash}.json"))
+}
+
+/// Reads the query's metadata from its path in the cache directory.
+pub fn read(path: &Path, name: &Ident) -> Result<QueryMetadata, String> {
+    let contents = fs::read_to_string(path).map_err(|error| {
+        format!(
+            "could not read the cached metadata for `{name}` at `{}`, run `sql-helper prepare`: {error}",
+            path.display()
+        )
+    })?;
+
+    serde_json::from_str(&contents).map_err(|error| {
+        format!(
+            "the cached metadata for `{name}` at `{}` is invalid, run `sql-helper prepare`: {error}",
+            path.display()
+        )
+    })
+}
+
+/// Writes the query's metadata to the cache directory.
+pub fn write(directory: &Path, name: &Ident, metadata: &QueryMetadata) -> Result<(), String> {
+    let path = metadata_path(directory, name, &metadata.hash);
+
+    let contents = serde_json::to_string_pretty(metadata)
+        .map_err(|error| format!("could not serialize the metadata for `{name}`: {error}"))?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|error| format!("could not create `{}`: {error}", parent.display()))?;
+    }
+
+    fs::write(&path, contents + "\n").map_err(|error| {
+        format!(
+            "could not write the metadata for `{name}` to `{}`: {error}",
+            path.display()
+        )
+    })
+}
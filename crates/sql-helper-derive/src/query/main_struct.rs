@@ -2,7 +2,12 @@ use proc_macro2::TokenStream;
 use quote::{format_ident, quote};
 use syn::Ident;
 
-pub fn create_main_struct(name: &Ident, query: &str, parameters: &[syn::Type]) -> TokenStream {
+pub fn create_main_struct(
+    name: &Ident,
+    query: &str,
+    parameters: &[syn::Type],
+    has_row: bool,
+) -> TokenStream {
     let parameter_fields: Vec<_> = parameters
         .iter()
         .enumerate()
@@ -23,6 +28,8 @@ pub fn create_main_struct(name: &Ident, query: &str, parameters: &[syn::Type]) -
     let self_parameter_names = parameter_names.iter().map(|param| quote!(&self.#param));
 
     let parameter_count = parameters.len();
+    let name_lit = name.to_string();
+    let row_methods = has_row.then(|| create_row_methods(name));
 
     quote! {
         struct #name<'a> {
@@ -31,6 +38,11 @@ pub fn create_main_struct(name: &Ident, query: &str, parameters: &[syn::Type]) -
         }
         impl<'a> #name<'a> {
             pub const QUERY: &'static str = #query;
+            /// Creates a span for executing the query, only recorded when `ts-sql-helper-lib` has
+            /// the `tracing` feature enabled.
+            pub fn span() -> ts_sql_helper_lib::QuerySpan {
+                ts_sql_helper_lib::QuerySpan::new(#name_lit, Self::QUERY)
+            }
             pub fn params(#( #parameter_fields ),*) -> Self {
                 Self {
                     #( #parameter_names , )*
@@ -43,6 +55,84 @@ pub fn create_main_struct(name: &Ident, query: &str, parameters: &[syn::Type]) -
                     #( #self_parameter_names , )*
                 ]
             }
+
+            /// Executes the query on the client, returns the number of rows modified.
+            pub fn execute<C: ts_sql_helper_lib::postgres::GenericClient>(
+                &'a self,
+                client: &mut C,
+            ) -> Result<u64, ts_sql_helper_lib::postgres::Error> {
+                Self::span().in_scope(|| client.execute(Self::QUERY, self.as_array().as_slice()))
+            }
+
+            #row_methods
+        }
+    }
+}
+
+/// Creates the methods that run the query on a client and convert the returned rows.
+fn create_row_methods(name: &Ident) -> TokenStream {
+    let row_name = format_ident!("{name}Row");
+
+    quote! {
+        /// Runs the query on the client, returns the converted rows.
+        pub fn query<C: ts_sql_helper_lib::postgres::GenericClient>(
+            &'a self,
+            client: &mut C,
+        ) -> Result<Vec<#row_name>, ts_sql_helper_lib::postgres::Error> {
+            Self::span().in_scope(|| {
+                client
+                    .query(Self::QUERY, self.as_array().as_slice())?
+                    .iter()
+                    .map(<#row_name as ts_sql_helper_lib::FromRow>::from_row)
+                    .collect()
+            })
+        }
+
+        /// Runs the query on the client, returns the converted row. Errors unless exactly one row
+        /// is returned.
+        pub fn query_one<C: ts_sql_helper_lib::postgres::GenericClient>(
+            &'a self,
+            client: &mut C,
+        ) -> Result<#row_name, ts_sql_helper_lib::postgres::Error> {
+            Self::span().in_scope(|| {
+                let row = client.query_one(Self::QUERY, self.as_array().as_slice())?;
+                <#row_name as ts_sql_helper_lib::FromRow>::from_row(&row)
+            })
+        }
+
+        /// Runs the query on the client, returns the converted row if one was returned. Errors if
+        /// more than one row is returned.
+        pub fn query_opt<C: ts_sql_helper_lib::postgres::GenericClient>(
+            &'a self,
+            client: &mut C,
+        ) -> Result<Option<#row_name>, ts_sql_helper_lib::postgres::Error> {
+            Self::span().in_scope(|| {
+                client
+                    .query_opt(Self::QUERY, self.as_array().as_slice())?
+                    .as_ref()
+                    .map(<#row_name as ts_sql_helper_lib::FromRow>::from_row)
+                    .transpose()
+            })
+        }
+
+        /// Runs the query on the client, returns an iterator that converts the rows as they are
+        /// received. Each row is converted inside the query's span.
+        pub fn query_iter<'c, C: ts_sql_helper_lib::postgres::GenericClient>(
+            &'a self,
+            client: &'c mut C,
+        ) -> Result<
+            impl Iterator<Item = Result<#row_name, ts_sql_helper_lib::postgres::Error>> + 'c,
+            ts_sql_helper_lib::postgres::Error,
+        > {
+            use ts_sql_helper_lib::postgres::fallible_iterator::FallibleIterator;
+
+            let span = Self::span();
+            let rows = span.in_scope(|| client.query_raw(Self::QUERY, self.as_array()))?;
+            Ok(rows.iterator().map(move |row| {
+                span.in_scope(|| {
+                    row.and_then(|row| <#row_name as ts_sql_helper_lib::FromRow>::from_row(&row))
+                })
+            }))
         }
     }
 }
@@ -4,10 +4,14 @@ use syn::{
     parse::{Parse, ParseStream},
 };
 
+#[cfg(feature = "verify")]
+pub mod cache;
 pub mod main_struct;
 pub mod parameters;
 pub mod row_struct;
 pub mod test;
+#[cfg(feature = "verify")]
+pub mod verify;
 
 pub struct RowField {
     pub name: Ident,
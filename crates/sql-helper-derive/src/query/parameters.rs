@@ -130,8 +130,8 @@ pub fn parameter_to_type(parameter_type: &Type) -> syn::Type {
         &Type::FLOAT8_ARRAY => parse_quote!(&'a [f64]),
         &Type::FLOAT4 => parse_quote!(&'a f32),
         &Type::FLOAT4_ARRAY => parse_quote!(&'a [f32]),
-        &Type::UUID => parse_quote!(&'a uuid::Uuid),
-        &Type::UUID_ARRAY => parse_quote!(&'a [uuid::Uuid]),
+        &Type::UUID => parse_quote!(&'a ts_sql_helper_lib::uuid::Uuid),
+        &Type::UUID_ARRAY => parse_quote!(&'a [ts_sql_helper_lib::uuid::Uuid]),
         &Type::TEXT | &Type::VARCHAR => parse_quote!(&'a str),
         &Type::VARCHAR_ARRAY | &Type::TEXT_ARRAY => parse_quote!(&'a [String]),
         &Type::TIMESTAMP => parse_quote!(&'a ts_sql_helper_lib::SqlDateTime),
@@ -14,3 +14,174 @@ pub fn create_row_struct(query_struct_name: &Ident, fields: &[RowField]) -> Toke
         }
     }
 }
+
+#[cfg(feature = "verify")]
+/// Infers the row fields from the columns of the verified query, nullable columns are wrapped in
+/// an `Option`.
+pub fn infer_row_fields(
+    metadata: &crate::query::cache::QueryMetadata,
+    literal: &syn::LitStr,
+) -> syn::Result<Vec<RowField>> {
+    metadata
+        .columns
+        .iter()
+        .map(|column| {
+            let name: Ident = syn::parse_str(&column.name).map_err(|_| {
+                syn::Error::new(
+                    literal.span(),
+                    format!(
+                        "column `{}` is not a valid field name, rename it with `AS` or write the `row` fields",
+                        column.name
+                    ),
+                )
+            })?;
+
+            let Some(r#type) = column_to_type(&column.r#type) else {
+                return Err(syn::Error::new(
+                    literal.span(),
+                    format!(
+                        "column `{}` has the unsupported type `{}`, write the `row` fields",
+                        column.name, column.r#type
+                    ),
+                ));
+            };
+
+            let r#type = if column.nullable {
+                syn::parse_quote!(Option<#r#type>)
+            } else {
+                r#type
+            };
+
+            Ok(RowField { name, r#type })
+        })
+        .collect()
+}
+
+#[cfg(feature = "verify")]
+/// The owned Rust type of a column with the PostgreSQL type name.
+fn column_to_type(type_name: &str) -> Option<syn::Type> {
+    use syn::parse_quote;
+
+    if let Some(element) = type_name.strip_prefix('_') {
+        let element = column_to_type(element)?;
+        return Some(parse_quote!(Vec<#element>));
+    }
+
+    let r#type = match type_name {
+        "bool" => parse_quote!(bool),
+        "bytea" => parse_quote!(Vec<u8>),
+        "char" => parse_quote!(i8),
+        "int8" => parse_quote!(i64),
+        "int4" => parse_quote!(i32),
+        "int2" => parse_quote!(i16),
+        "float8" => parse_quote!(f64),
+        "float4" => parse_quote!(f32),
+        "uuid" => parse_quote!(ts_sql_helper_lib::uuid::Uuid),
+        "text" | "varchar" => parse_quote!(String),
+        "timestamp" => parse_quote!(ts_sql_helper_lib::SqlDateTime),
+        "timestamptz" => parse_quote!(ts_sql_helper_lib::SqlTimestamp),
+        "date" => parse_quote!(ts_sql_helper_lib::SqlDate),
+        "time" => parse_quote!(ts_sql_helper_lib::SqlTime),
+        _ => return None,
+    };
+
+    Some(r#type)
+}
+
+#[cfg(all(test, feature = "verify"))]
+mod tests {
+    use quote::ToTokens;
+
+    use super::{column_to_type, infer_row_fields};
+    use crate::query::cache::{ColumnMetadata, QueryMetadata};
+
+    fn type_string(r#type: Option<syn::Type>) -> Option<String> {
+        r#type.map(|r#type| r#type.to_token_stream().to_string())
+    }
+
+    fn metadata(columns: &[(&str, &str, bool)]) -> QueryMetadata {
+        QueryMetadata {
+            hash: String::new(),
+            query: String::new(),
+            parameters: Vec::new(),
+            columns: columns
+                .iter()
+                .map(|(name, r#type, nullable)| ColumnMetadata {
+                    name: name.to_string(),
+                    r#type: r#type.to_string(),
+                    nullable: *nullable,
+                })
+                .collect(),
+        }
+    }
+
+    fn literal() -> syn::LitStr {
+        syn::parse_quote!("SELECT")
+    }
+
+    #[test]
+    fn maps_array_elements() {
+        assert_eq!(
+            type_string(column_to_type("_int4")),
+            Some("Vec < i32 >".to_string())
+        );
+        assert_eq!(
+            type_string(column_to_type("_uuid")),
+            Some("Vec < ts_sql_helper_lib :: uuid :: Uuid >".to_string())
+        );
+        assert_eq!(
+            type_string(column_to_type("_bytea")),
+            Some("Vec < Vec < u8 > >".to_string())
+        );
+        assert_eq!(type_string(column_to_type("_jsonb")), None);
+    }
+
+    #[test]
+    fn wraps_nullable_columns() {
+        let fields = infer_row_fields(
+            &metadata(&[("id", "int4", false), ("name", "varchar", true)]),
+            &literal(),
+        )
+        .unwrap();
+
+        let fields: Vec<(String, String)> = fields
+            .iter()
+            .map(|field| {
+                (
+                    field.name.to_string(),
+                    field.r#type.to_token_stream().to_string(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                ("id".to_string(), "i32".to_string()),
+                ("name".to_string(), "Option < String >".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_field_names() {
+        for name in ["?column?", "user id", "type"] {
+            let error = infer_row_fields(&metadata(&[(name, "int4", false)]), &literal())
+                .err()
+                .unwrap();
+
+            assert!(
+                error.to_string().contains("is not a valid field name"),
+                "`{name}` should be rejected: {error}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_unsupported_types() {
+        let error = infer_row_fields(&metadata(&[("data", "jsonb", false)]), &literal())
+            .err()
+            .unwrap();
+
+        assert!(error.to_string().contains("unsupported type `jsonb`"));
+    }
+}
@@ -1,9 +1,12 @@
 use proc_macro2::TokenStream;
-use quote::{format_ident, quote};
+use quote::{ToTokens, format_ident, quote};
 use syn::Ident;
 
-pub fn create_test(struct_name: &Ident) -> TokenStream {
+use crate::query::RowField;
+
+pub fn create_test(struct_name: &Ident, row: Option<&[RowField]>) -> TokenStream {
     let test_name = format_ident!("test_{struct_name}");
+    let row_test = row.map(|fields| create_row_test(struct_name, fields));
 
     quote! {
         #[cfg(test)]
@@ -13,10 +16,13 @@ pub fn create_test(struct_name: &Ident) -> TokenStream {
             use ts_sql_helper_lib::test::get_test_database;
 
             let (mut client, _container) = get_test_database();
-            let statement = client.prepare(#struct_name::QUERY);
+            let span = #struct_name::span();
+            let statement = span.in_scope(|| client.prepare(#struct_name::QUERY));
             assert!(statement.is_ok(), "invalid query `{}`: {}", #struct_name::QUERY, statement.unwrap_err());
             let statement = statement.unwrap();
 
+            #row_test
+
             let mut data: Vec<Box<dyn ts_sql_helper_lib::postgres_types::ToSql + Sync>> = Vec::new();
             let params = statement.params();
             for param in params.iter() {
@@ -29,7 +35,7 @@ pub fn create_test(struct_name: &Ident) -> TokenStream {
             let borrowed_data: Vec<&(dyn ts_sql_helper_lib::postgres_types::ToSql + Sync)> =
                 data.iter().map(|data| data.as_ref()).collect();
 
-            let result = client.execute(&statement, borrowed_data.as_slice());
+            let result = span.in_scope(|| client.execute(&statement, borrowed_data.as_slice()));
             if let Err(error) = result {
                 use ts_sql_helper_lib::postgres::error::SqlState;
 
@@ -45,3 +51,60 @@ pub fn create_test(struct_name: &Ident) -> TokenStream {
         }
     }
 }
+
+/// Checks that the row fields match the names, count, and types of the statement's columns.
+fn create_row_test(struct_name: &Ident, fields: &[RowField]) -> TokenStream {
+    let row_name = format!("{struct_name}Row");
+    let field_names = fields.iter().map(|field| field.name.to_string());
+    let field_type_names = fields
+        .iter()
+        .map(|field| field.r#type.to_token_stream().to_string());
+    let field_types = fields.iter().map(|field| &field.r#type);
+
+    quote! {
+        let fields: &[(&str, &str, fn(&ts_sql_helper_lib::postgres_types::Type) -> bool)] = &[
+            #( (
+                #field_names,
+                #field_type_names,
+                <#field_types as ts_sql_helper_lib::postgres_types::FromSql<'_>>::accepts,
+            ), )*
+        ];
+        let columns = statement.columns();
+
+        let mut mismatches: Vec<String> = Vec::new();
+        if fields.len() != columns.len() {
+            mismatches.push(format!(
+                "expected {} columns, found {}",
+                fields.len(),
+                columns.len()
+            ));
+        }
+        for (name, type_name, accepts) in fields {
+            match columns.iter().find(|column| column.name() == *name) {
+                Some(column) if !accepts(column.type_()) => mismatches.push(format!(
+                    "field `{name}: {type_name}` does not accept column type `{}`",
+                    column.type_().name()
+                )),
+                Some(_) => {}
+                None => mismatches.push(format!("field `{name}` has no matching column")),
+            }
+        }
+        for column in columns {
+            if !fields.iter().any(|(name, ..)| *name == column.name()) {
+                mismatches.push(format!(
+                    "column `{}: {}` has no matching field",
+                    column.name(),
+                    column.type_().name()
+                ));
+            }
+        }
+
+        assert!(
+            mismatches.is_empty(),
+            "`{}` does not match the columns of `{}`:\n{}",
+            #row_name,
+            #struct_name::QUERY,
+            mismatches.join("\n")
+        );
+    }
+}
@@ -0,0 +1,170 @@
+//! Verifying queries at compile time by preparing them against a database, or against the cached
+//! metadata written by `sql-helper prepare`.
+//!
+
+use std::{
+    env,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use syn::{Ident, LitStr};
+
+use crate::query::cache::{self, ColumnMetadata, PREPARE_DIRECTORY, QueryMetadata};
+
+/// The environment variable containing the connection string of the database to verify against.
+pub const DATABASE_URL: &str = "DATABASE_URL";
+
+/// The environment variable that, when set to `1` or `true`, verifies queries against the cached
+/// metadata even if `DATABASE_URL` is set.
+pub const OFFLINE: &str = "SQL_HELPER_OFFLINE";
+
+/// Selects if a table's column has a `NOT NULL` constraint.
+const SELECT_NOT_NULL: &str =
+    "SELECT attnotnull FROM pg_attribute WHERE attrelid = $1::OID AND attnum = $2::INT2;";
+
+/// A query that was verified.
+pub struct VerifiedQuery {
+    /// The metadata the query was verified against.
+    pub metadata: QueryMetadata,
+    /// The path of the cached metadata the query was verified against, if it was read from the
+    /// cache.
+    pub cache_path: Option<PathBuf>,
+}
+
+/// The connection that is reused by every query verified by this compiler process.
+static CLIENT: Mutex<Option<postgres::Client>> = Mutex::new(None);
+
+/// Verifies the query, errors at the query literal if the query is invalid. Returns the metadata
+/// the query was verified against, if it was verified.
+///
+/// If `DATABASE_URL` is set, the query is prepared against the database, and the metadata is
+/// written to the cache directory when run by `sql-helper prepare`. Otherwise, if a cache directory
+/// exists, the query must match its cached metadata. Queries are not verified if neither exist.
+///
+/// If `SQL_HELPER_OFFLINE` is set, the cached metadata is always used and must exist.
+pub fn verify_query(
+    name: &Ident,
+    literal: &LitStr,
+    query: &str,
+) -> syn::Result<Option<VerifiedQuery>> {
+    let offline = env::var(OFFLINE).is_ok_and(|value| value == "1" || value == "true");
+
+    if !offline && let Ok(database_url) = env::var(DATABASE_URL) {
+        let metadata = prepare_query(&database_url, literal, query)?;
+
+        if let Ok(directory) = env::var(PREPARE_DIRECTORY) {
+            cache::write(Path::new(&directory), name, &metadata)
+                .map_err(|message| syn::Error::new(literal.span(), message))?;
+        }
+
+        return Ok(Some(VerifiedQuery {
+            metadata,
+            cache_path: None,
+        }));
+    }
+
+    if let Some(directory) = cache::find_directory() {
+        let hash = cache::hash(query);
+        let path = cache::metadata_path(&directory, name, &hash);
+        let metadata =
+            cache::read(&path, name).map_err(|message| syn::Error::new(literal.span(), message))?;
+
+        if metadata.hash != hash {
+            return Err(syn::Error::new(
+                literal.span(),
+                format!(
+                    "the cached metadata for `{name}` was prepared from a different query, run `sql-helper prepare`"
+                ),
+            ));
+        }
+
+        return Ok(Some(VerifiedQuery {
+            metadata,
+            cache_path: Some(path),
+        }));
+    }
+
+    if offline {
+        return Err(syn::Error::new(
+            literal.span(),
+            format!(
+                "`{OFFLINE}` is set but there is no `{}` directory, run `sql-helper prepare`",
+                cache::CACHE_DIRECTORY
+            ),
+        ));
+    }
+
+    Ok(None)
+}
+
+/// Prepares the query against the database, returns the metadata of the prepared statement.
+fn prepare_query(database_url: &str, literal: &LitStr, query: &str) -> syn::Result<QueryMetadata> {
+    let mut client = CLIENT
+        .lock()
+        .map_err(|_| syn::Error::new(literal.span(), "query verification connection poisoned"))?;
+
+    if client.as_ref().is_none_or(postgres::Client::is_closed) {
+        let connection =
+            postgres::Client::connect(database_url, postgres::NoTls).map_err(|error| {
+                syn::Error::new(
+                    literal.span(),
+                    format!("could not connect to `{DATABASE_URL}` to verify the query: {error}"),
+                )
+            })?;
+        *client = Some(connection);
+    }
+
+    let Some(client) = client.as_mut() else {
+        return Err(syn::Error::new(
+            literal.span(),
+            "query verification connection missing",
+        ));
+    };
+
+    let statement = client.prepare(query).map_err(|error| {
+        let message = error
+            .as_db_error()
+            .map_or_else(|| error.to_string(), |error| error.message().to_string());
+
+        syn::Error::new(literal.span(), format!("invalid query: {message}"))
+    })?;
+
+    let mut columns = Vec::with_capacity(statement.columns().len());
+    for column in statement.columns() {
+        // Columns that are not read directly from a table, such as expressions, may always be null.
+        let nullable = match (column.table_oid(), column.column_id()) {
+            (Some(table), Some(column_id)) => client
+                .query_opt(SELECT_NOT_NULL, &[&table, &column_id])
+                .and_then(|row| row.map_or(Ok(false), |row| row.try_get::<_, bool>(0)))
+                .map(|not_null| !not_null)
+                .map_err(|error| {
+                    syn::Error::new(
+                        literal.span(),
+                        format!(
+                            "could not read the nullability of column `{}`: {error}",
+                            column.name()
+                        ),
+                    )
+                })?,
+            _ => true,
+        };
+
+        columns.push(ColumnMetadata {
+            name: column.name().to_string(),
+            r#type: column.type_().name().to_string(),
+            nullable,
+        });
+    }
+
+    Ok(QueryMetadata {
+        hash: cache::hash(query),
+        query: query.to_string(),
+        parameters: statement
+            .params()
+            .iter()
+            .map(|r#type| r#type.name().to_string())
+            .collect(),
+        columns,
+    })
+}
@@ -0,0 +1,26 @@
+#![allow(missing_docs)]
+
+use ts_sql_helper_derive::embed_migrations;
+use ts_sql_helper_lib::EmbeddedMigration;
+
+const MIGRATIONS: &[EmbeddedMigration] = embed_migrations!("migrations");
+
+#[test]
+fn embeds_migrations() {
+    assert_eq!(MIGRATIONS.len(), 1);
+    assert_eq!(MIGRATIONS[0].name, "001.sql");
+    assert_eq!(MIGRATIONS[0].sql, include_str!("../migrations/001.sql"));
+}
+
+const NESTED_MIGRATIONS: &[EmbeddedMigration] = embed_migrations!("tests/nested_migrations");
+
+#[test]
+fn embeds_nested_migrations() {
+    assert_eq!(NESTED_MIGRATIONS.len(), 2);
+    assert_eq!(NESTED_MIGRATIONS[0].name, "1_users.sql");
+    assert_eq!(NESTED_MIGRATIONS[1].name, "2_posts.sql");
+    assert_eq!(
+        NESTED_MIGRATIONS[1].sql,
+        include_str!("nested_migrations/posts/2_posts.sql")
+    );
+}
@@ -0,0 +1 @@
+CREATE TABLE IF NOT EXISTS users (id INT4 PRIMARY KEY);
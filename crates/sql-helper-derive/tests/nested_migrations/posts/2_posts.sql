@@ -0,0 +1 @@
+CREATE TABLE IF NOT EXISTS posts (id INT4 PRIMARY KEY);
@@ -60,3 +60,35 @@ fn real_test() {
     assert_eq!(row.challenge, vec![0, 1, 2, 3, 4]);
     assert_eq!(row.origin, "some-origin");
 }
+
+#[test]
+fn generated_methods() {
+    let (mut client, _container) = ts_sql_helper_lib::test::get_test_database();
+
+    let rows_modified = CreateChallenge::params(&[5, 6, 7], "other-origin")
+        .execute(&mut client)
+        .unwrap();
+    assert_eq!(rows_modified, 1);
+
+    let params = GetChallenge::params(&[5, 6, 7]);
+
+    let rows = params.query(&mut client).unwrap();
+    assert_eq!(rows.len(), 1);
+
+    let row = params.query_one(&mut client).unwrap();
+    assert_eq!(row.challenge, vec![5, 6, 7]);
+    assert_eq!(row.origin, "other-origin");
+
+    let row = params.query_opt(&mut client).unwrap();
+    assert!(row.is_some());
+
+    let rows: Vec<_> = params
+        .query_iter(&mut client)
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(rows.len(), 1);
+
+    let row = GetChallenge::params(&[8]).query_opt(&mut client).unwrap();
+    assert!(row.is_none());
+}
@@ -0,0 +1,61 @@
+//! Optional instrumentation of SQL activity using `tracing`, enabled by the `tracing` feature.
+//!
+//! Without the feature every helper is a no-op, so generated code can use them unconditionally.
+//!
+
+/// A span covering the execution of a query, created by the generated `span()` of a `query!`
+/// struct.
+#[derive(Debug, Clone)]
+pub struct QuerySpan {
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+}
+
+impl QuerySpan {
+    /// Creates a span for the query with the name of the query struct.
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    pub fn new(name: &'static str, query: &'static str) -> Self {
+        Self {
+            #[cfg(feature = "tracing")]
+            span: tracing::info_span!("query", query.name = name, query.sql = query),
+        }
+    }
+
+    /// Runs the function inside the span.
+    pub fn in_scope<T>(&self, f: impl FnOnce() -> T) -> T {
+        #[cfg(feature = "tracing")]
+        return self.span.in_scope(f);
+
+        #[cfg(not(feature = "tracing"))]
+        f()
+    }
+
+    #[cfg(feature = "tracing")]
+    /// The underlying span, such as to instrument a future.
+    pub fn span(&self) -> &tracing::Span {
+        &self.span
+    }
+}
+
+#[doc(hidden)]
+/// Records a failure to convert a column of a row, used by the `FromRow` derive.
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+pub fn from_row_failed(type_name: &str, column: &str, error: &postgres::Error) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(
+        row.type_name = type_name,
+        row.column = column,
+        error = %error,
+        "could not convert row"
+    );
+}
+
+#[cfg(feature = "tracing")]
+/// Creates the span covering a migration.
+pub(crate) fn migration_span(namespace: &str, name: &str) -> tracing::Span {
+    tracing::info_span!(
+        "migration",
+        migration.namespace = namespace,
+        migration.name = name
+    )
+}
@@ -3,6 +3,8 @@
 
 mod error;
 mod from_row;
+#[doc(hidden)]
+pub mod instrument;
 mod migrations;
 mod postgres_types_jiff_0_2;
 #[cfg(feature = "test")]
@@ -10,14 +12,36 @@ pub mod test;
 
 pub use error::SqlError;
 pub use from_row::{FromRow, ParseFromRow};
+pub use instrument::QuerySpan;
 #[cfg(feature = "async")]
-pub use migrations::perform_migrations_async;
-pub use migrations::{MigrationError, perform_migrations};
+pub use migrations::{
+    AsyncMigration, migration_status_async, migration_status_from_source_async,
+    migration_status_with_options_async, perform_embedded_migrations_async,
+    perform_migration_sets_async, perform_migrations_async, perform_migrations_from_source_async,
+    perform_migrations_with_options_async, repair_migrations_async,
+    repair_migrations_from_source_async, repair_migrations_with_options_async,
+    rollback_migrations_async, rollback_migrations_from_source_async,
+    rollback_migrations_with_options_async,
+};
+pub use migrations::{
+    BASELINE_SUFFIX, CodeMigrationError, DEFAULT_LOCK_KEY, DEFAULT_NAMESPACE, DOWN_SUFFIX,
+    EmbeddedMigration, EmbeddedSource, FilesystemSource, InMemorySource, Migration, MigrationError,
+    MigrationFile, MigrationLocation, MigrationObserver, MigrationOptions, MigrationReport,
+    MigrationSet, MigrationSetSource, MigrationSource, MigrationSourceError, MigrationState,
+    MigrationStatus, NO_TRANSACTION_MARKER, REPEATABLE_PREFIX, discover_migrations,
+    discover_migrations_from_source, migration_status, migration_status_from_source,
+    migration_status_with_options, migration_version, perform_embedded_migrations,
+    perform_migration_sets, perform_migrations, perform_migrations_from_source,
+    perform_migrations_with_options, repair_migrations, repair_migrations_from_source,
+    repair_migrations_with_options, rollback_migrations, rollback_migrations_from_source,
+    rollback_migrations_with_options,
+};
 pub use postgres_types_jiff_0_2::{SqlDate, SqlDateTime, SqlTime, SqlTimestamp};
 
 pub use postgres;
 pub use postgres_protocol;
 pub use postgres_types;
+pub use uuid;
 
 #[cfg(feature = "derive")]
-pub use ts_sql_helper_derive::{FromRow, FromSql, query};
+pub use ts_sql_helper_derive::{FromRow, FromSql, embed_migrations, query};
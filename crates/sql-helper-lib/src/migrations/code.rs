@@ -0,0 +1,58 @@
+//! Migrations written in Rust, for changes such as data backfills that are awkward in SQL.
+//!
+
+#[cfg(feature = "async")]
+use core::{future::Future, pin::Pin};
+
+/// The error returned by a failed code migration.
+pub type CodeMigrationError = Box<dyn core::error::Error + Send + Sync>;
+
+/// A migration written in Rust that runs alongside the SQL migrations.
+///
+/// Code migrations are named `<version>_<description>` like the SQL migration files, and are
+/// ordered by the parsed version alongside them. A name without a version is rejected as
+/// [`MalformedVersion`][crate::MigrationError::MalformedVersion]. They are recorded in the same
+/// history and always run inside a transaction.
+pub trait Migration: Send + Sync {
+    /// The name of the migration.
+    fn name(&self) -> &str;
+
+    /// Applies the migration.
+    fn up(&self, transaction: &mut postgres::Transaction<'_>) -> Result<(), CodeMigrationError>;
+
+    /// Reverts the migration, by default errors as the migration cannot be reverted.
+    fn down(&self, transaction: &mut postgres::Transaction<'_>) -> Result<(), CodeMigrationError> {
+        let _ = transaction;
+        Err(format!("code migration `{}` has no down migration", self.name()).into())
+    }
+}
+
+#[cfg(feature = "async")]
+/// A migration written in Rust that runs alongside the SQL migrations.
+///
+/// Code migrations are named `<version>_<description>` like the SQL migration files, and are
+/// ordered by the parsed version alongside them. A name without a version is rejected as
+/// [`MalformedVersion`][crate::MigrationError::MalformedVersion]. They are recorded in the same
+/// history and always run inside a transaction.
+pub trait AsyncMigration: Send + Sync {
+    /// The name of the migration.
+    fn name(&self) -> &str;
+
+    /// Applies the migration.
+    fn up<'a>(
+        &'a self,
+        transaction: &'a tokio_postgres::Transaction<'_>,
+    ) -> Pin<Box<dyn Future<Output = Result<(), CodeMigrationError>> + Send + 'a>>;
+
+    /// Reverts the migration, by default errors as the migration cannot be reverted.
+    fn down<'a>(
+        &'a self,
+        transaction: &'a tokio_postgres::Transaction<'_>,
+    ) -> Pin<Box<dyn Future<Output = Result<(), CodeMigrationError>> + Send + 'a>> {
+        let _ = transaction;
+        let name = self.name().to_string();
+        Box::pin(
+            async move { Err(format!("code migration `{name}` has no down migration").into()) },
+        )
+    }
+}
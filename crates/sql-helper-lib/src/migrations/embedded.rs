@@ -0,0 +1,41 @@
+//! Migrations that are embedded into the binary at compile time.
+//!
+
+#[cfg(feature = "async")]
+use crate::migrations::perform_migrations_from_source_async;
+use crate::migrations::{
+    EmbeddedSource, MigrationError, MigrationOptions, perform_migrations_from_source,
+};
+
+/// A migration file that was embedded into the binary, usually created by the
+/// `embed_migrations!` macro.
+#[derive(Debug, Clone, Copy)]
+pub struct EmbeddedMigration {
+    /// The file name of the migration.
+    pub name: &'static str,
+    /// The contents of the migration.
+    pub sql: &'static str,
+}
+
+/// Runs the embedded migrations on the client using the options, returns the names of the applied
+/// migrations. This has the same semantics as
+/// [`perform_migrations_with_options`][crate::perform_migrations_with_options].
+pub fn perform_embedded_migrations(
+    client: &mut postgres::Client,
+    migrations: &[EmbeddedMigration],
+    options: &MigrationOptions,
+) -> Result<Vec<String>, MigrationError> {
+    perform_migrations_from_source(client, &EmbeddedSource::new(migrations), options)
+}
+
+#[cfg(feature = "async")]
+/// Runs the embedded migrations on the client using the options, returns the names of the applied
+/// migrations. This has the same semantics as
+/// [`perform_migrations_with_options_async`][crate::perform_migrations_with_options_async].
+pub async fn perform_embedded_migrations_async(
+    client: &mut tokio_postgres::Client,
+    migrations: &[EmbeddedMigration],
+    options: &MigrationOptions,
+) -> Result<Vec<String>, MigrationError> {
+    perform_migrations_from_source_async(client, &EmbeddedSource::new(migrations), options).await
+}
@@ -0,0 +1,361 @@
+//! Bookkeeping for the migrations that have been applied to a database.
+//!
+
+use std::{collections::HashMap, path::PathBuf};
+
+use postgres::Row;
+use sha2::{Digest, Sha256};
+
+use crate::migrations::{
+    BASELINE_SUFFIX, DEFAULT_NAMESPACE, MigrationError, MigrationTarget, REPEATABLE_PREFIX,
+    migration_version,
+};
+
+/// A down migration that reverts an applied migration.
+pub struct Reversal {
+    pub namespace: String,
+    pub name: String,
+    pub path: Option<PathBuf>,
+    pub sql: String,
+    /// The index of the code migration in the registered migrations of the runner's flavour, that
+    /// is reverted by its `down` instead of the SQL.
+    pub code: Option<usize>,
+}
+
+/// The `(namespace, name)` pair that identifies an applied migration.
+pub type HistoryKey = (String, String);
+
+/// Creates the history table if it does not exist.
+pub const CREATE_TABLE: &str = r#"
+    CREATE TABLE IF NOT EXISTS _sql_helper_migrations (
+        namespace VARCHAR NOT NULL,
+        name VARCHAR NOT NULL,
+        checksum BYTEA NOT NULL,
+        applied_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+        reverted_at TIMESTAMP WITH TIME ZONE,
+        PRIMARY KEY (namespace, name)
+    );"#;
+
+/// Selects if the history table exists.
+pub const HISTORY_EXISTS: &str =
+    "SELECT to_regclass('_sql_helper_migrations') IS NOT NULL AS history_exists;";
+
+/// Selects the namespaces, names, and checksums of the applied migrations that have not been
+/// reverted.
+pub const SELECT_APPLIED: &str =
+    "SELECT namespace, name, checksum FROM _sql_helper_migrations WHERE reverted_at IS NULL;";
+
+/// Records a migration as applied, replacing any previous reversal.
+pub const INSERT_APPLIED: &str = r#"
+    INSERT INTO _sql_helper_migrations (namespace, name, checksum)
+    VALUES ($1::VARCHAR, $2::VARCHAR, $3::BYTEA)
+    ON CONFLICT (namespace, name) DO UPDATE SET
+        checksum = EXCLUDED.checksum,
+        applied_at = EXCLUDED.applied_at,
+        reverted_at = NULL;"#;
+
+/// Records a migration as reverted.
+pub const UPDATE_REVERTED: &str = r#"
+    UPDATE _sql_helper_migrations
+    SET reverted_at = NOW()
+    WHERE namespace = $1::VARCHAR AND name = $2::VARCHAR;"#;
+
+/// Replaces the checksum of an applied migration.
+pub const UPDATE_CHECKSUM: &str = r#"
+    UPDATE _sql_helper_migrations
+    SET checksum = $3::BYTEA
+    WHERE namespace = $1::VARCHAR AND name = $2::VARCHAR;"#;
+
+/// Calculates the checksum of a migration's contents.
+pub fn checksum(sql: &str) -> Vec<u8> {
+    Sha256::digest(sql.as_bytes()).to_vec()
+}
+
+/// Reads the checksum of each applied migration from the history rows.
+pub fn applied_checksums(applied: &[Row]) -> Result<HashMap<HistoryKey, Vec<u8>>, MigrationError> {
+    applied
+        .iter()
+        .map(|row| {
+            let key = (row.try_get("namespace")?, row.try_get("name")?);
+            Ok((key, row.try_get("checksum")?))
+        })
+        .collect::<Result<_, postgres::Error>>()
+        .map_err(|source| MigrationError::ReadHistory { source })
+}
+
+/// Filters the targets down to the migrations that have not been recorded in the history, and the
+/// repeatable migrations whose contents have changed since they were last applied.
+///
+/// In a namespace without any applied migrations, the latest baseline replaces the migrations it
+/// covers, otherwise baselines are dropped.
+///
+/// Errors if an applied migration's contents no longer match the recorded checksum.
+pub fn pending_migrations(
+    targets: Vec<MigrationTarget>,
+    applied: &[Row],
+) -> Result<Vec<MigrationTarget>, MigrationError> {
+    let applied = applied_checksums(applied)?;
+    let targets = apply_baselines(targets, &applied);
+
+    if let Some(target) = targets.iter().find(|target| {
+        !target.repeatable
+            && applied
+                .get(&target.key())
+                .is_some_and(|checksum| checksum != &target.checksum)
+    }) {
+        return Err(MigrationError::ChecksumMismatch {
+            name: target.name.clone(),
+        });
+    }
+
+    Ok(targets
+        .into_iter()
+        .filter(|target| {
+            applied
+                .get(&target.key())
+                .is_none_or(|checksum| target.repeatable && checksum != &target.checksum)
+        })
+        .collect())
+}
+
+/// Replaces the migrations covered by the latest baseline of each namespace without any applied
+/// migrations, and drops every other baseline.
+fn apply_baselines(
+    targets: Vec<MigrationTarget>,
+    applied: &HashMap<HistoryKey, Vec<u8>>,
+) -> Vec<MigrationTarget> {
+    let is_fresh = |namespace: &str| !applied.keys().any(|(applied, _)| applied == namespace);
+
+    let mut baselines: HashMap<String, u64> = HashMap::new();
+    for target in targets.iter().filter(|target| target.baseline) {
+        if is_fresh(&target.namespace) {
+            let version = baselines.entry(target.namespace.clone()).or_default();
+            *version = (*version).max(target.version);
+        }
+    }
+
+    let mut result: Vec<MigrationTarget> = Vec::with_capacity(targets.len());
+    let mut replaced: Vec<MigrationTarget> = Vec::new();
+    for target in targets {
+        let baseline_version = baselines.get(&target.namespace).copied();
+        if target.baseline {
+            if baseline_version == Some(target.version) {
+                result.push(target);
+            }
+        } else if !target.repeatable
+            && baseline_version.is_some_and(|version| target.version <= version)
+        {
+            replaced.push(target);
+        } else {
+            result.push(target);
+        }
+    }
+
+    for target in replaced {
+        if let Some(baseline) = result
+            .iter_mut()
+            .find(|baseline| baseline.baseline && baseline.namespace == target.namespace)
+        {
+            baseline.replaces.push((target.name, target.checksum));
+        }
+    }
+
+    result
+}
+
+/// Filters the targets down to the applied migrations whose contents no longer match the recorded
+/// checksum.
+pub fn modified_migrations(
+    targets: Vec<MigrationTarget>,
+    applied: &[Row],
+) -> Result<Vec<MigrationTarget>, MigrationError> {
+    let applied = applied_checksums(applied)?;
+
+    Ok(targets
+        .into_iter()
+        .filter(|target| {
+            !target.repeatable
+                && applied
+                    .get(&target.key())
+                    .is_some_and(|checksum| checksum != &target.checksum)
+        })
+        .collect())
+}
+
+/// Collects the down migrations that revert the applied migrations in the default namespace with a
+/// version after the target, in reverse version order. Repeatable migrations are not reverted, and
+/// code migrations are reverted by their `down`.
+///
+/// Baselines are not reverted, the migrations they replaced are recorded as applied and are
+/// reverted by their own down migrations instead.
+///
+/// Errors if an applied SQL migration that must be reverted has no down migration.
+pub fn reversals(
+    targets: Vec<MigrationTarget>,
+    applied: &[Row],
+    target: Option<u64>,
+) -> Result<Vec<Reversal>, MigrationError> {
+    let applied = applied_checksums(applied)?;
+
+    let mut names: Vec<(u64, &String)> = applied
+        .keys()
+        .filter(|(namespace, name)| {
+            namespace == DEFAULT_NAMESPACE
+                && !name.starts_with(REPEATABLE_PREFIX)
+                && !name.ends_with(BASELINE_SUFFIX)
+        })
+        .map(|(_, name)| Ok((migration_version(name)?, name)))
+        .collect::<Result<_, MigrationError>>()?;
+    names.retain(|(version, _)| target.is_none_or(|target| *version > target));
+    names.sort();
+
+    names
+        .into_iter()
+        .rev()
+        .map(|(_, name)| {
+            let target = targets
+                .iter()
+                .find(|target| target.namespace == DEFAULT_NAMESPACE && &target.name == name);
+
+            if let Some(index) = target.and_then(|target| target.code) {
+                return Ok(Reversal {
+                    namespace: DEFAULT_NAMESPACE.to_string(),
+                    name: name.clone(),
+                    path: None,
+                    sql: String::new(),
+                    code: Some(index),
+                });
+            }
+
+            let down = target
+                .and_then(|target| target.down.clone())
+                .ok_or_else(|| MigrationError::MissingDownMigration { name: name.clone() })?;
+
+            Ok(Reversal {
+                namespace: DEFAULT_NAMESPACE.to_string(),
+                name: name.clone(),
+                path: down.path,
+                sql: down.sql,
+                code: None,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{HistoryKey, apply_baselines, checksum};
+    use crate::migrations::{DEFAULT_NAMESPACE, MigrationFile, MigrationTarget, migration_targets};
+
+    fn targets(namespace: &str, files: &[&str]) -> Vec<MigrationTarget> {
+        let files = files
+            .iter()
+            .map(|name| MigrationFile::new(*name, format!("-- {name}")))
+            .collect();
+
+        let mut targets = migration_targets(files).unwrap();
+        for target in &mut targets {
+            target.namespace = namespace.to_string();
+        }
+        targets
+    }
+
+    fn applied(keys: &[(&str, &str)]) -> HashMap<HistoryKey, Vec<u8>> {
+        keys.iter()
+            .map(|(namespace, name)| {
+                let key = (namespace.to_string(), name.to_string());
+                (key, checksum(&format!("-- {name}")))
+            })
+            .collect()
+    }
+
+    fn names(targets: &[MigrationTarget]) -> Vec<&str> {
+        targets.iter().map(|target| target.name.as_str()).collect()
+    }
+
+    const FILES: &[&str] = &[
+        "1_users.sql",
+        "1_baseline.baseline.sql",
+        "2_posts.sql",
+        "2_baseline.baseline.sql",
+        "3_comments.sql",
+        "R__views.sql",
+    ];
+
+    #[test]
+    fn fresh_namespace_uses_latest_baseline() {
+        let result = apply_baselines(targets(DEFAULT_NAMESPACE, FILES), &HashMap::new());
+
+        assert_eq!(
+            names(&result),
+            ["2_baseline.baseline.sql", "3_comments.sql", "R__views.sql"]
+        );
+        assert_eq!(
+            result[0].replaces,
+            [
+                ("1_users.sql".to_string(), checksum("-- 1_users.sql")),
+                ("2_posts.sql".to_string(), checksum("-- 2_posts.sql")),
+            ]
+        );
+    }
+
+    #[test]
+    fn applied_namespace_drops_baselines() {
+        let result = apply_baselines(
+            targets(DEFAULT_NAMESPACE, FILES),
+            &applied(&[(DEFAULT_NAMESPACE, "1_users.sql")]),
+        );
+
+        assert_eq!(
+            names(&result),
+            [
+                "1_users.sql",
+                "2_posts.sql",
+                "3_comments.sql",
+                "R__views.sql"
+            ]
+        );
+        assert!(result.iter().all(|target| target.replaces.is_empty()));
+    }
+
+    #[test]
+    fn baselines_are_chosen_per_namespace() {
+        let mut all = targets("users", FILES);
+        all.extend(targets("billing", FILES));
+
+        let result = apply_baselines(all, &applied(&[("users", "1_users.sql")]));
+        let namespaced: Vec<(&str, &str)> = result
+            .iter()
+            .map(|target| (target.namespace.as_str(), target.name.as_str()))
+            .collect();
+
+        assert_eq!(
+            namespaced,
+            [
+                ("users", "1_users.sql"),
+                ("users", "2_posts.sql"),
+                ("users", "3_comments.sql"),
+                ("users", "R__views.sql"),
+                ("billing", "2_baseline.baseline.sql"),
+                ("billing", "3_comments.sql"),
+                ("billing", "R__views.sql"),
+            ]
+        );
+    }
+
+    #[test]
+    fn migrations_after_baseline_are_kept() {
+        let result = apply_baselines(
+            targets(
+                DEFAULT_NAMESPACE,
+                &["1_users.sql", "1_baseline.baseline.sql", "2_posts.sql"],
+            ),
+            &HashMap::new(),
+        );
+
+        assert_eq!(names(&result), ["1_baseline.baseline.sql", "2_posts.sql"]);
+        assert_eq!(result[0].replaces.len(), 1);
+    }
+}
@@ -0,0 +1,155 @@
+//! Locating the statement of a migration that failed to execute.
+//!
+
+use postgres::error::ErrorPosition;
+
+/// Where a migration failed to execute, derived from the error position reported by the server, or
+/// the start of the migration's only statement if the server reports no position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MigrationLocation {
+    /// The line of the executed SQL that failed, starting from 1.
+    pub line: usize,
+    /// The column of the executed SQL that failed in characters, starting from 1.
+    pub column: usize,
+    /// The contents of the line that failed.
+    pub source_line: String,
+    /// The statement that failed.
+    pub statement: String,
+}
+impl core::fmt::Display for MigrationLocation {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let gutter = " ".repeat(self.line.to_string().len());
+        writeln!(f, "{gutter} |")?;
+        writeln!(f, "{} | {}", self.line, self.source_line)?;
+        write!(f, "{gutter} | {}^", " ".repeat(self.column - 1))
+    }
+}
+
+/// Locates the failing statement in the executed SQL from the error position reported by the
+/// server. If the server reports no position, such as for constraint violations, the statement is
+/// only located if the SQL contains a single statement.
+///
+/// Statements are delimited by the nearest `;` on either side of the position, which may be
+/// inaccurate when a `;` appears inside a string or function body.
+pub fn locate(sql: &str, error: &postgres::Error) -> Option<MigrationLocation> {
+    let position = match error.as_db_error()?.position() {
+        Some(ErrorPosition::Original(position)) => usize::try_from(*position).ok(),
+        Some(ErrorPosition::Internal { .. }) | None => None,
+    };
+
+    locate_position(sql, position)
+}
+
+/// Locates the statement at the position in characters starting from 1, or the start of the only
+/// statement in the SQL if there is no position.
+fn locate_position(sql: &str, position: Option<usize>) -> Option<MigrationLocation> {
+    let offset = match position {
+        Some(position) => sql.char_indices().nth(position.checked_sub(1)?)?.0,
+        None => single_statement_offset(sql)?,
+    };
+
+    let line_start = sql[..offset].rfind('\n').map_or(0, |index| index + 1);
+    let line_end = sql[offset..]
+        .find('\n')
+        .map_or(sql.len(), |index| offset + index);
+
+    let statement_start = sql[..offset].rfind(';').map_or(0, |index| index + 1);
+    let statement_end = sql[offset..]
+        .find(';')
+        .map_or(sql.len(), |index| offset + index + 1);
+
+    Some(MigrationLocation {
+        line: sql[..offset].matches('\n').count() + 1,
+        column: sql[line_start..offset].chars().count() + 1,
+        source_line: sql[line_start..line_end].trim_end().to_string(),
+        statement: sql[statement_start..statement_end].trim().to_string(),
+    })
+}
+
+/// The offset of the start of the only statement in the SQL, `None` if the SQL does not contain
+/// exactly one statement.
+fn single_statement_offset(sql: &str) -> Option<usize> {
+    let mut start = None;
+    let mut offset = 0;
+    for statement in sql.split(';') {
+        if let Some(index) = code_start(statement) {
+            if start.is_some() {
+                return None;
+            }
+            start = Some(offset + index);
+        }
+        offset += statement.len() + 1;
+    }
+
+    start
+}
+
+/// The offset of the first character that is not whitespace or part of a `--` comment.
+fn code_start(text: &str) -> Option<usize> {
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let code = line.split_once("--").map_or(line, |(code, _comment)| code);
+        if let Some(index) = code.find(|character: char| !character.is_whitespace()) {
+            return Some(offset + index);
+        }
+        offset += line.len();
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MigrationLocation, locate_position};
+
+    #[test]
+    fn locates_position_in_statement() {
+        let sql = "CREATE TABLE a (id INT4);\nSELECT * FROM missing;\n";
+
+        assert_eq!(
+            locate_position(sql, Some(41)),
+            Some(MigrationLocation {
+                line: 2,
+                column: 15,
+                source_line: "SELECT * FROM missing;".to_string(),
+                statement: "SELECT * FROM missing;".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn counts_columns_in_characters() {
+        let sql = "SELECT 'é';\nSELECT 'ü', missing;";
+
+        let location = locate_position(sql, Some(25)).unwrap();
+        assert_eq!(location.line, 2);
+        assert_eq!(location.column, 13);
+        assert_eq!(location.statement, "SELECT 'ü', missing;");
+        assert_eq!(
+            location.to_string(),
+            "  |\n2 | SELECT 'ü', missing;\n  |             ^"
+        );
+    }
+
+    #[test]
+    fn locates_single_statement_without_position() {
+        let sql = "-- Divides by zero\n  SELECT 1 / 0;\n-- Done\n";
+
+        let location = locate_position(sql, None).unwrap();
+        assert_eq!(location.line, 2);
+        assert_eq!(location.column, 3);
+        assert_eq!(location.statement, "-- Divides by zero\n  SELECT 1 / 0;");
+    }
+
+    #[test]
+    fn does_not_locate_multiple_statements_without_position() {
+        assert_eq!(locate_position("SELECT 1;\nSELECT 1 / 0;", None), None);
+    }
+
+    #[test]
+    fn rejects_position_outside_sql() {
+        assert_eq!(locate_position("SELECT 1;", Some(0)), None);
+        assert_eq!(locate_position("SELECT 1;", Some(11)), None);
+    }
+}
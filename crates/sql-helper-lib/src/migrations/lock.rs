@@ -0,0 +1,100 @@
+//! Advisory lock that guards the migration runner against concurrent runs.
+//!
+
+use crate::migrations::{MigrationError, MigrationOptions};
+
+/// Acquires the session level advisory lock, waiting until it is available.
+const ACQUIRE_LOCK: &str = "SELECT pg_advisory_lock($1::INT8);";
+
+/// Releases the session level advisory lock.
+const RELEASE_LOCK: &str = "SELECT pg_advisory_unlock($1::INT8);";
+
+/// Limits how long acquiring the lock may wait for, scoped to the transaction that acquires it.
+fn set_lock_timeout(options: &MigrationOptions) -> Option<String> {
+    options.lock_timeout.map(|timeout| {
+        // A timeout of zero disables the timeout, so wait at least a millisecond.
+        let milliseconds = timeout.as_millis().max(1);
+        format!("SET LOCAL lock_timeout = {milliseconds};")
+    })
+}
+
+/// Acquires the migration lock, the lock is held by the session until it is released.
+pub fn acquire(
+    client: &mut postgres::Client,
+    options: &MigrationOptions,
+) -> Result<(), MigrationError> {
+    let mut transaction = client
+        .transaction()
+        .map_err(|source| MigrationError::AcquireLock { source })?;
+
+    if let Some(set_lock_timeout) = set_lock_timeout(options) {
+        transaction
+            .batch_execute(&set_lock_timeout)
+            .map_err(|source| MigrationError::AcquireLock { source })?;
+    }
+
+    transaction
+        .execute(ACQUIRE_LOCK, &[&options.lock_key])
+        .map_err(|source| MigrationError::AcquireLock { source })?;
+    transaction
+        .commit()
+        .map_err(|source| MigrationError::AcquireLock { source })?;
+
+    Ok(())
+}
+
+/// Releases the migration lock.
+pub fn release(
+    client: &mut postgres::Client,
+    options: &MigrationOptions,
+) -> Result<(), MigrationError> {
+    client
+        .execute(RELEASE_LOCK, &[&options.lock_key])
+        .map_err(|source| MigrationError::ReleaseLock { source })?;
+
+    Ok(())
+}
+
+#[cfg(feature = "async")]
+/// Acquires the migration lock, the lock is held by the session until it is released.
+pub async fn acquire_async(
+    client: &mut tokio_postgres::Client,
+    options: &MigrationOptions,
+) -> Result<(), MigrationError> {
+    let transaction = client
+        .transaction()
+        .await
+        .map_err(|source| MigrationError::AcquireLock { source })?;
+
+    if let Some(set_lock_timeout) = set_lock_timeout(options) {
+        transaction
+            .batch_execute(&set_lock_timeout)
+            .await
+            .map_err(|source| MigrationError::AcquireLock { source })?;
+    }
+
+    transaction
+        .execute(ACQUIRE_LOCK, &[&options.lock_key])
+        .await
+        .map_err(|source| MigrationError::AcquireLock { source })?;
+    transaction
+        .commit()
+        .await
+        .map_err(|source| MigrationError::AcquireLock { source })?;
+
+    Ok(())
+}
+
+#[cfg(feature = "async")]
+/// Releases the migration lock.
+pub async fn release_async(
+    client: &tokio_postgres::Client,
+    options: &MigrationOptions,
+) -> Result<(), MigrationError> {
+    client
+        .execute(RELEASE_LOCK, &[&options.lock_key])
+        .await
+        .map_err(|source| MigrationError::ReleaseLock { source })?;
+
+    Ok(())
+}
@@ -0,0 +1,1576 @@
+//! Helpers for running migrations
+//!
+//! Migrations are `<version>_<description>.sql` files that are executed in version order, where the
+//! version is an unsigned integer and the description is optional. A migration may be split into a
+//! `<version>_<description>.up.sql` and `<version>_<description>.down.sql` pair, where the down
+//! file reverts the up file.
+//!
+//! Migration directories are searched recursively, so migrations may be grouped into
+//! subdirectories. Independent groups of migrations, such as those owned by different crates, can
+//! be run as [`MigrationSet`]s that are each tracked under their own namespace.
+//! Migrations may also be loaded from anywhere else through a [`MigrationSource`].
+//!
+//! A `<version>_<description>.baseline.sql` file is a snapshot of the schema after every migration
+//! up to and including its version, usually created by squashing the migrations. A fresh database
+//! is migrated by applying the latest baseline in place of the migrations it covers, which are
+//! then recorded as applied. Baselines are ignored once any migration has been applied.
+//!
+//! An `R__<description>.sql` file is a repeatable migration, such as a view or function, that is
+//! re-applied after the versioned migrations of its namespace whenever its contents change.
+//! Repeatable migrations run in name order.
+//!
+//! Migration files may contain `${variable}` placeholders that are replaced with the values in
+//! [`MigrationOptions::variables`] before they are executed.
+//!
+
+use core::time::Duration;
+use std::{
+    collections::{HashMap, HashSet},
+    env::current_dir,
+    ffi::OsStr,
+    fs, io,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use postgres::{SimpleQueryMessage, error::SqlState};
+
+mod code;
+mod embedded;
+mod history;
+mod location;
+mod lock;
+mod observer;
+mod options;
+mod sets;
+mod source;
+mod status;
+mod template;
+
+#[cfg(feature = "async")]
+pub use code::AsyncMigration;
+pub use code::{CodeMigrationError, Migration};
+#[cfg(feature = "async")]
+pub use embedded::perform_embedded_migrations_async;
+pub use embedded::{EmbeddedMigration, perform_embedded_migrations};
+pub use location::MigrationLocation;
+pub use observer::{MigrationObserver, MigrationReport};
+pub use options::{DEFAULT_LOCK_KEY, MigrationOptions};
+#[cfg(feature = "async")]
+pub use sets::perform_migration_sets_async;
+pub use sets::{MigrationSet, MigrationSetSource, perform_migration_sets};
+pub use source::{
+    EmbeddedSource, FilesystemSource, InMemorySource, MigrationSource, MigrationSourceError,
+    discover_migrations_from_source, perform_migrations_from_source, repair_migrations_from_source,
+    rollback_migrations_from_source,
+};
+#[cfg(feature = "async")]
+pub use source::{
+    perform_migrations_from_source_async, repair_migrations_from_source_async,
+    rollback_migrations_from_source_async,
+};
+pub use status::{
+    MigrationState, MigrationStatus, migration_status, migration_status_from_source,
+    migration_status_with_options,
+};
+#[cfg(feature = "async")]
+pub use status::{
+    migration_status_async, migration_status_from_source_async, migration_status_with_options_async,
+};
+
+/// Marker that opts a migration file out of running inside a transaction, required for statements
+/// such as `CREATE INDEX CONCURRENTLY`.
+///
+/// A marked file must contain exactly one statement. The file is sent as a single query, and
+/// PostgreSQL runs a query with multiple statements inside an implicit transaction, so statements
+/// that cannot run in a transaction fail. Split such statements into one migration file each.
+pub const NO_TRANSACTION_MARKER: &str = "-- sql-helper: no-transaction";
+
+/// The namespace that migrations are recorded under when they are not run as part of a named
+/// [`MigrationSet`].
+pub const DEFAULT_NAMESPACE: &str = "default";
+
+/// Runs the migrations in `current_dir()/migrations/*.sql` on the client, migrations are executed
+/// in version order.
+///
+/// Applied migrations are recorded in the `_sql_helper_migrations` table, only migrations that
+/// have not been recorded are executed.
+pub fn perform_migrations(
+    client: &mut postgres::Client,
+    migrations_directory: Option<PathBuf>,
+) -> Result<(), MigrationError> {
+    perform_migrations_with_options(client, migrations_directory, &MigrationOptions::default())?;
+
+    Ok(())
+}
+
+/// Runs the migrations in `current_dir()/migrations/*.sql` on the client using the options,
+/// migrations are executed in version order. Returns the names of the applied migrations, or the
+/// migrations that would have been applied for a dry run.
+///
+/// Each migration runs inside its own transaction unless it contains the
+/// [`NO_TRANSACTION_MARKER`], or the options request a single transaction for the whole batch.
+///
+/// The migrations are guarded by an advisory lock, so concurrent callers wait for the running
+/// migrations to complete and then only apply what is still pending.
+pub fn perform_migrations_with_options(
+    client: &mut postgres::Client,
+    migrations_directory: Option<PathBuf>,
+    options: &MigrationOptions,
+) -> Result<Vec<String>, MigrationError> {
+    let source = FilesystemSource {
+        directory: migrations_directory,
+        strict: options.strict,
+    };
+
+    perform_migrations_from_source(client, &source, options)
+}
+
+/// Applies the targets that have not been recorded in the history, returns the names of the
+/// applied migrations.
+fn apply_pending_migrations(
+    client: &mut postgres::Client,
+    targets: Vec<MigrationTarget>,
+    options: &MigrationOptions,
+) -> Result<Vec<String>, MigrationError> {
+    if options.single_transaction || options.dry_run {
+        let mut transaction = client
+            .transaction()
+            .map_err(|source| MigrationError::BeginTransaction { source })?;
+
+        let pending = read_pending_migrations(&mut transaction, targets, &options.variables)?;
+        if let Some(target) = pending.iter().find(|target| !target.transaction) {
+            return Err(MigrationError::TransactionDisabled {
+                name: target.name.clone(),
+            });
+        }
+        for target in &pending {
+            apply_migration(&mut transaction, target, options)?;
+        }
+
+        if options.dry_run {
+            transaction
+                .rollback()
+                .map_err(|source| MigrationError::RollbackTransaction { source })?;
+        } else {
+            transaction
+                .commit()
+                .map_err(|source| MigrationError::CommitTransaction { source })?;
+        }
+
+        return Ok(pending
+            .into_iter()
+            .map(|target| target.qualified_name())
+            .collect());
+    }
+
+    let pending = read_pending_migrations(client, targets, &options.variables)?;
+    for target in &pending {
+        if !target.transaction {
+            apply_migration(client, target, options)?;
+            continue;
+        }
+
+        let mut transaction = client
+            .transaction()
+            .map_err(|source| MigrationError::BeginTransaction { source })?;
+        apply_migration(&mut transaction, target, options)?;
+        transaction
+            .commit()
+            .map_err(|source| MigrationError::CommitTransaction { source })?;
+    }
+
+    Ok(pending
+        .into_iter()
+        .map(|target| target.qualified_name())
+        .collect())
+}
+
+/// Creates the history table if required, then filters the targets down to the migrations that
+/// have not been recorded in the history and renders their templates.
+fn read_pending_migrations<C: postgres::GenericClient>(
+    client: &mut C,
+    targets: Vec<MigrationTarget>,
+    variables: &HashMap<String, String>,
+) -> Result<Vec<MigrationTarget>, MigrationError> {
+    client
+        .batch_execute(history::CREATE_TABLE)
+        .map_err(|source| MigrationError::CreateHistory { source })?;
+    let applied = client
+        .query(history::SELECT_APPLIED, &[])
+        .map_err(|source| MigrationError::ReadHistory { source })?;
+
+    let pending = history::pending_migrations(targets, &applied)?;
+    template::render_targets(pending, variables)
+}
+
+#[cfg(feature = "async")]
+/// Runs the migrations in `current_dir()/migrations/*.sql` on the client, migrations are executed
+/// in version order.
+///
+/// Applied migrations are recorded in the `_sql_helper_migrations` table, only migrations that
+/// have not been recorded are executed.
+pub async fn perform_migrations_async(
+    client: &mut tokio_postgres::Client,
+    migrations_directory: Option<PathBuf>,
+) -> Result<(), MigrationError> {
+    perform_migrations_with_options_async(
+        client,
+        migrations_directory,
+        &MigrationOptions::default(),
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(feature = "async")]
+/// Runs the migrations in `current_dir()/migrations/*.sql` on the client using the options,
+/// migrations are executed in version order. Returns the names of the applied migrations, or the
+/// migrations that would have been applied for a dry run.
+///
+/// Each migration runs inside its own transaction unless it contains the
+/// [`NO_TRANSACTION_MARKER`], or the options request a single transaction for the whole batch.
+///
+/// The migrations are guarded by an advisory lock, so concurrent callers wait for the running
+/// migrations to complete and then only apply what is still pending.
+pub async fn perform_migrations_with_options_async(
+    client: &mut tokio_postgres::Client,
+    migrations_directory: Option<PathBuf>,
+    options: &MigrationOptions,
+) -> Result<Vec<String>, MigrationError> {
+    let source = FilesystemSource {
+        directory: migrations_directory,
+        strict: options.strict,
+    };
+
+    perform_migrations_from_source_async(client, &source, options).await
+}
+
+#[cfg(feature = "async")]
+/// Applies the targets that have not been recorded in the history, returns the names of the
+/// applied migrations.
+async fn apply_pending_migrations_async(
+    client: &mut tokio_postgres::Client,
+    targets: Vec<MigrationTarget>,
+    options: &MigrationOptions,
+) -> Result<Vec<String>, MigrationError> {
+    if options.single_transaction || options.dry_run {
+        let mut transaction = client
+            .transaction()
+            .await
+            .map_err(|source| MigrationError::BeginTransaction { source })?;
+
+        let pending =
+            read_pending_migrations_async(&transaction, targets, &options.variables).await?;
+        if let Some(target) = pending.iter().find(|target| !target.transaction) {
+            return Err(MigrationError::TransactionDisabled {
+                name: target.name.clone(),
+            });
+        }
+        for target in &pending {
+            apply_migration_async(&mut transaction, target, options).await?;
+        }
+
+        if options.dry_run {
+            transaction
+                .rollback()
+                .await
+                .map_err(|source| MigrationError::RollbackTransaction { source })?;
+        } else {
+            transaction
+                .commit()
+                .await
+                .map_err(|source| MigrationError::CommitTransaction { source })?;
+        }
+
+        return Ok(pending
+            .into_iter()
+            .map(|target| target.qualified_name())
+            .collect());
+    }
+
+    let pending = read_pending_migrations_async(client, targets, &options.variables).await?;
+    for target in &pending {
+        if !target.transaction {
+            apply_migration_async(client, target, options).await?;
+            continue;
+        }
+
+        let mut transaction = client
+            .transaction()
+            .await
+            .map_err(|source| MigrationError::BeginTransaction { source })?;
+        apply_migration_async(&mut transaction, target, options).await?;
+        transaction
+            .commit()
+            .await
+            .map_err(|source| MigrationError::CommitTransaction { source })?;
+    }
+
+    Ok(pending
+        .into_iter()
+        .map(|target| target.qualified_name())
+        .collect())
+}
+
+#[cfg(feature = "async")]
+/// Creates the history table if required, then filters the targets down to the migrations that
+/// have not been recorded in the history and renders their templates.
+async fn read_pending_migrations_async<C: tokio_postgres::GenericClient>(
+    client: &C,
+    targets: Vec<MigrationTarget>,
+    variables: &HashMap<String, String>,
+) -> Result<Vec<MigrationTarget>, MigrationError> {
+    client
+        .batch_execute(history::CREATE_TABLE)
+        .await
+        .map_err(|source| MigrationError::CreateHistory { source })?;
+    let applied = client
+        .query(history::SELECT_APPLIED, &[])
+        .await
+        .map_err(|source| MigrationError::ReadHistory { source })?;
+
+    let pending = history::pending_migrations(targets, &applied)?;
+    template::render_targets(pending, variables)
+}
+
+/// Accepts the current contents of every applied migration in `current_dir()/migrations/*.sql` by
+/// replacing the recorded checksums, returns the names of the repaired migrations.
+///
+/// Errors if the migrations directory does not exist, as the applied migrations cannot be compared
+/// against it.
+///
+/// This should only be used after confirming that the edits to the applied migrations are intended.
+pub fn repair_migrations(
+    client: &mut postgres::Client,
+    migrations_directory: Option<PathBuf>,
+) -> Result<Vec<String>, MigrationError> {
+    repair_migrations_with_options(client, migrations_directory, &MigrationOptions::default())
+}
+
+/// Accepts the current contents of every applied migration in `current_dir()/migrations/*.sql` by
+/// replacing the recorded checksums, returns the names of the repaired migrations.
+///
+/// The repair is guarded by the same advisory lock as the migration runner.
+///
+/// Errors if the migrations directory does not exist, as the applied migrations cannot be compared
+/// against it.
+///
+/// This should only be used after confirming that the edits to the applied migrations are intended.
+pub fn repair_migrations_with_options(
+    client: &mut postgres::Client,
+    migrations_directory: Option<PathBuf>,
+    options: &MigrationOptions,
+) -> Result<Vec<String>, MigrationError> {
+    let source = FilesystemSource {
+        directory: migrations_directory,
+        strict: true,
+    };
+
+    repair_migrations_from_source(client, &source, options)
+}
+
+/// Replaces the recorded checksums of the applied targets whose contents have changed.
+fn repair_targets(
+    client: &mut postgres::Client,
+    targets: Vec<MigrationTarget>,
+) -> Result<Vec<String>, MigrationError> {
+    client
+        .batch_execute(history::CREATE_TABLE)
+        .map_err(|source| MigrationError::CreateHistory { source })?;
+    let applied = client
+        .query(history::SELECT_APPLIED, &[])
+        .map_err(|source| MigrationError::ReadHistory { source })?;
+
+    let mut repaired = Vec::new();
+    for target in history::modified_migrations(targets, &applied)? {
+        client
+            .execute(
+                history::UPDATE_CHECKSUM,
+                &[&target.namespace, &target.name, &target.checksum],
+            )
+            .map_err(|source| MigrationError::WriteHistory {
+                source,
+                name: target.name.clone(),
+            })?;
+        repaired.push(target.name);
+    }
+
+    Ok(repaired)
+}
+
+#[cfg(feature = "async")]
+/// Accepts the current contents of every applied migration in `current_dir()/migrations/*.sql` by
+/// replacing the recorded checksums, returns the names of the repaired migrations.
+///
+/// Errors if the migrations directory does not exist, as the applied migrations cannot be compared
+/// against it.
+///
+/// This should only be used after confirming that the edits to the applied migrations are intended.
+pub async fn repair_migrations_async(
+    client: &mut tokio_postgres::Client,
+    migrations_directory: Option<PathBuf>,
+) -> Result<Vec<String>, MigrationError> {
+    repair_migrations_with_options_async(client, migrations_directory, &MigrationOptions::default())
+        .await
+}
+
+#[cfg(feature = "async")]
+/// Accepts the current contents of every applied migration in `current_dir()/migrations/*.sql` by
+/// replacing the recorded checksums, returns the names of the repaired migrations.
+///
+/// The repair is guarded by the same advisory lock as the migration runner.
+///
+/// Errors if the migrations directory does not exist, as the applied migrations cannot be compared
+/// against it.
+///
+/// This should only be used after confirming that the edits to the applied migrations are intended.
+pub async fn repair_migrations_with_options_async(
+    client: &mut tokio_postgres::Client,
+    migrations_directory: Option<PathBuf>,
+    options: &MigrationOptions,
+) -> Result<Vec<String>, MigrationError> {
+    let source = FilesystemSource {
+        directory: migrations_directory,
+        strict: true,
+    };
+
+    repair_migrations_from_source_async(client, &source, options).await
+}
+
+#[cfg(feature = "async")]
+/// Replaces the recorded checksums of the applied targets whose contents have changed.
+async fn repair_targets_async(
+    client: &mut tokio_postgres::Client,
+    targets: Vec<MigrationTarget>,
+) -> Result<Vec<String>, MigrationError> {
+    client
+        .batch_execute(history::CREATE_TABLE)
+        .await
+        .map_err(|source| MigrationError::CreateHistory { source })?;
+    let applied = client
+        .query(history::SELECT_APPLIED, &[])
+        .await
+        .map_err(|source| MigrationError::ReadHistory { source })?;
+
+    let mut repaired = Vec::new();
+    for target in history::modified_migrations(targets, &applied)? {
+        client
+            .execute(
+                history::UPDATE_CHECKSUM,
+                &[&target.namespace, &target.name, &target.checksum],
+            )
+            .await
+            .map_err(|source| MigrationError::WriteHistory {
+                source,
+                name: target.name.clone(),
+            })?;
+        repaired.push(target.name);
+    }
+
+    Ok(repaired)
+}
+
+/// Reverts the applied migrations in `current_dir()/migrations/*.sql` with a version after the
+/// target in reverse version order using their `*.down.sql` files, returns the names of the reverted
+/// migrations.
+///
+/// Errors if the migrations directory does not exist, as it contains the down migrations.
+///
+/// If the target is `None`, every applied migration is reverted. Each reversal runs inside its own
+/// transaction unless the down file contains the [`NO_TRANSACTION_MARKER`].
+pub fn rollback_migrations(
+    client: &mut postgres::Client,
+    migrations_directory: Option<PathBuf>,
+    target: Option<u64>,
+) -> Result<Vec<String>, MigrationError> {
+    rollback_migrations_with_options(
+        client,
+        migrations_directory,
+        target,
+        &MigrationOptions::default(),
+    )
+}
+
+/// Reverts the applied migrations in `current_dir()/migrations/*.sql` with a version after the
+/// target in reverse version order using their `*.down.sql` files, returns the names of the reverted
+/// migrations.
+///
+/// Errors if the migrations directory does not exist, as it contains the down migrations.
+///
+/// If the target is `None`, every applied migration is reverted. Each reversal runs inside its own
+/// transaction unless the down file contains the [`NO_TRANSACTION_MARKER`]. The rollback is guarded
+/// by the same advisory lock as the migration runner.
+///
+/// Applied code migrations are reverted by the `down` of the matching registered migration.
+pub fn rollback_migrations_with_options(
+    client: &mut postgres::Client,
+    migrations_directory: Option<PathBuf>,
+    target: Option<u64>,
+    options: &MigrationOptions,
+) -> Result<Vec<String>, MigrationError> {
+    let source = FilesystemSource {
+        directory: migrations_directory,
+        strict: true,
+    };
+
+    rollback_migrations_from_source(client, &source, target, options)
+}
+
+/// Reverts the applied targets with a version after the target in reverse version order.
+fn rollback_targets(
+    client: &mut postgres::Client,
+    targets: Vec<MigrationTarget>,
+    target: Option<u64>,
+    options: &MigrationOptions,
+) -> Result<Vec<String>, MigrationError> {
+    client
+        .batch_execute(history::CREATE_TABLE)
+        .map_err(|source| MigrationError::CreateHistory { source })?;
+    let applied = client
+        .query(history::SELECT_APPLIED, &[])
+        .map_err(|source| MigrationError::ReadHistory { source })?;
+
+    let mut reverted = Vec::new();
+    for reversal in history::reversals(targets, &applied, target)? {
+        if !runs_in_transaction(&reversal.sql) {
+            revert_migration(client, &reversal, options)?;
+            reverted.push(reversal.name);
+            continue;
+        }
+
+        let mut transaction = client
+            .transaction()
+            .map_err(|source| MigrationError::BeginTransaction { source })?;
+        revert_migration(&mut transaction, &reversal, options)?;
+        transaction
+            .commit()
+            .map_err(|source| MigrationError::CommitTransaction { source })?;
+        reverted.push(reversal.name);
+    }
+
+    Ok(reverted)
+}
+
+#[cfg(feature = "async")]
+/// Reverts the applied migrations in `current_dir()/migrations/*.sql` with a version after the
+/// target in reverse version order using their `*.down.sql` files, returns the names of the reverted
+/// migrations.
+///
+/// Errors if the migrations directory does not exist, as it contains the down migrations.
+///
+/// If the target is `None`, every applied migration is reverted. Each reversal runs inside its own
+/// transaction unless the down file contains the [`NO_TRANSACTION_MARKER`].
+pub async fn rollback_migrations_async(
+    client: &mut tokio_postgres::Client,
+    migrations_directory: Option<PathBuf>,
+    target: Option<u64>,
+) -> Result<Vec<String>, MigrationError> {
+    rollback_migrations_with_options_async(
+        client,
+        migrations_directory,
+        target,
+        &MigrationOptions::default(),
+    )
+    .await
+}
+
+#[cfg(feature = "async")]
+/// Reverts the applied migrations in `current_dir()/migrations/*.sql` with a version after the
+/// target in reverse version order using their `*.down.sql` files, returns the names of the reverted
+/// migrations.
+///
+/// Errors if the migrations directory does not exist, as it contains the down migrations.
+///
+/// If the target is `None`, every applied migration is reverted. Each reversal runs inside its own
+/// transaction unless the down file contains the [`NO_TRANSACTION_MARKER`]. The rollback is guarded
+/// by the same advisory lock as the migration runner.
+///
+/// Applied code migrations are reverted by the `down` of the matching registered migration.
+pub async fn rollback_migrations_with_options_async(
+    client: &mut tokio_postgres::Client,
+    migrations_directory: Option<PathBuf>,
+    target: Option<u64>,
+    options: &MigrationOptions,
+) -> Result<Vec<String>, MigrationError> {
+    let source = FilesystemSource {
+        directory: migrations_directory,
+        strict: true,
+    };
+
+    rollback_migrations_from_source_async(client, &source, target, options).await
+}
+
+#[cfg(feature = "async")]
+/// Reverts the applied targets with a version after the target in reverse version order.
+async fn rollback_targets_async(
+    client: &mut tokio_postgres::Client,
+    targets: Vec<MigrationTarget>,
+    target: Option<u64>,
+    options: &MigrationOptions,
+) -> Result<Vec<String>, MigrationError> {
+    client
+        .batch_execute(history::CREATE_TABLE)
+        .await
+        .map_err(|source| MigrationError::CreateHistory { source })?;
+    let applied = client
+        .query(history::SELECT_APPLIED, &[])
+        .await
+        .map_err(|source| MigrationError::ReadHistory { source })?;
+
+    let mut reverted = Vec::new();
+    for reversal in history::reversals(targets, &applied, target)? {
+        if !runs_in_transaction(&reversal.sql) {
+            revert_migration_async(client, &reversal, options).await?;
+            reverted.push(reversal.name);
+            continue;
+        }
+
+        let mut transaction = client
+            .transaction()
+            .await
+            .map_err(|source| MigrationError::BeginTransaction { source })?;
+        revert_migration_async(&mut transaction, &reversal, options).await?;
+        transaction
+            .commit()
+            .await
+            .map_err(|source| MigrationError::CommitTransaction { source })?;
+        reverted.push(reversal.name);
+    }
+
+    Ok(reverted)
+}
+
+/// Executes a migration and records it in the history, if the client is a transaction, dropping it
+/// on error rolls the migration back.
+///
+/// Code migrations run inside a transaction nested in the client.
+fn apply_migration<C: postgres::GenericClient>(
+    client: &mut C,
+    target: &MigrationTarget,
+    options: &MigrationOptions,
+) -> Result<(), MigrationError> {
+    #[cfg(feature = "tracing")]
+    let _span = crate::instrument::migration_span(&target.namespace, &target.name).entered();
+
+    let name = target.qualified_name();
+    if let Some(observer) = &options.observer {
+        observer.before_migration(&name);
+    }
+    let start = Instant::now();
+
+    let rows_affected = if let Some(index) = target.code {
+        let mut transaction = client
+            .transaction()
+            .map_err(|source| MigrationError::BeginTransaction { source })?;
+        options.migrations[index]
+            .up(&mut transaction)
+            .map_err(|source| MigrationError::ExecuteCodeMigration {
+                source,
+                name: target.name.clone(),
+            })?;
+        transaction
+            .commit()
+            .map_err(|source| MigrationError::CommitTransaction { source })?;
+
+        None
+    } else {
+        let messages = client.simple_query(&target.sql).map_err(|source| {
+            MigrationError::execute(source, &target.name, target.path.as_ref(), &target.sql)
+        })?;
+
+        Some(rows_affected(&messages))
+    };
+    let duration = start.elapsed();
+
+    for (name, checksum) in target.recorded() {
+        client
+            .execute(
+                history::INSERT_APPLIED,
+                &[&target.namespace, &name, &checksum],
+            )
+            .map_err(|source| MigrationError::WriteHistory {
+                source,
+                name: name.to_string(),
+            })?;
+    }
+
+    report_applied(options, name, duration, rows_affected);
+
+    Ok(())
+}
+
+#[cfg(feature = "async")]
+/// Executes a migration and records it in the history, if the client is a transaction, dropping it
+/// on error rolls the migration back.
+///
+/// Code migrations run inside a transaction nested in the client.
+async fn apply_migration_async<C: tokio_postgres::GenericClient>(
+    client: &mut C,
+    target: &MigrationTarget,
+    options: &MigrationOptions,
+) -> Result<(), MigrationError> {
+    let apply = execute_migration_async(client, target, options);
+    #[cfg(feature = "tracing")]
+    let apply = tracing::Instrument::instrument(
+        apply,
+        crate::instrument::migration_span(&target.namespace, &target.name),
+    );
+
+    apply.await
+}
+
+#[cfg(feature = "async")]
+/// The body of [`apply_migration_async`], which wraps it in the migration's span.
+async fn execute_migration_async<C: tokio_postgres::GenericClient>(
+    client: &mut C,
+    target: &MigrationTarget,
+    options: &MigrationOptions,
+) -> Result<(), MigrationError> {
+    let name = target.qualified_name();
+    if let Some(observer) = &options.observer {
+        observer.before_migration(&name);
+    }
+    let start = Instant::now();
+
+    let rows_affected = if let Some(index) = target.code {
+        let transaction = client
+            .transaction()
+            .await
+            .map_err(|source| MigrationError::BeginTransaction { source })?;
+        options.async_migrations[index]
+            .up(&transaction)
+            .await
+            .map_err(|source| MigrationError::ExecuteCodeMigration {
+                source,
+                name: target.name.clone(),
+            })?;
+        transaction
+            .commit()
+            .await
+            .map_err(|source| MigrationError::CommitTransaction { source })?;
+
+        None
+    } else {
+        let messages = client.simple_query(&target.sql).await.map_err(|source| {
+            MigrationError::execute(source, &target.name, target.path.as_ref(), &target.sql)
+        })?;
+
+        Some(rows_affected(&messages))
+    };
+    let duration = start.elapsed();
+
+    for (name, checksum) in target.recorded() {
+        client
+            .execute(
+                history::INSERT_APPLIED,
+                &[&target.namespace, &name, &checksum],
+            )
+            .await
+            .map_err(|source| MigrationError::WriteHistory {
+                source,
+                name: name.to_string(),
+            })?;
+    }
+
+    report_applied(options, name, duration, rows_affected);
+
+    Ok(())
+}
+
+/// Sums the rows affected by each statement of a simple query.
+fn rows_affected(messages: &[SimpleQueryMessage]) -> u64 {
+    messages
+        .iter()
+        .map(|message| match message {
+            SimpleQueryMessage::CommandComplete(rows) => *rows,
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Reports an applied migration to the observer of the options.
+fn report_applied(
+    options: &MigrationOptions,
+    name: String,
+    duration: Duration,
+    rows_affected: Option<u64>,
+) {
+    if let Some(observer) = &options.observer {
+        observer.after_migration(&MigrationReport {
+            name,
+            duration,
+            rows_affected,
+        });
+    }
+}
+
+/// Executes a down migration and records the reversal in the history.
+fn revert_migration<C: postgres::GenericClient>(
+    client: &mut C,
+    reversal: &history::Reversal,
+    options: &MigrationOptions,
+) -> Result<(), MigrationError> {
+    if let Some(index) = reversal.code {
+        let mut transaction = client
+            .transaction()
+            .map_err(|source| MigrationError::BeginTransaction { source })?;
+        options.migrations[index]
+            .down(&mut transaction)
+            .map_err(|source| MigrationError::ExecuteCodeMigration {
+                source,
+                name: reversal.name.clone(),
+            })?;
+        transaction
+            .commit()
+            .map_err(|source| MigrationError::CommitTransaction { source })?;
+    } else {
+        client.batch_execute(&reversal.sql).map_err(|source| {
+            MigrationError::execute(
+                source,
+                &reversal.name,
+                reversal.path.as_ref(),
+                &reversal.sql,
+            )
+        })?;
+    }
+    client
+        .execute(
+            history::UPDATE_REVERTED,
+            &[&reversal.namespace, &reversal.name],
+        )
+        .map_err(|source| MigrationError::WriteHistory {
+            source,
+            name: reversal.name.clone(),
+        })?;
+
+    Ok(())
+}
+
+#[cfg(feature = "async")]
+/// Executes a down migration and records the reversal in the history.
+async fn revert_migration_async<C: tokio_postgres::GenericClient>(
+    client: &mut C,
+    reversal: &history::Reversal,
+    options: &MigrationOptions,
+) -> Result<(), MigrationError> {
+    if let Some(index) = reversal.code {
+        let transaction = client
+            .transaction()
+            .await
+            .map_err(|source| MigrationError::BeginTransaction { source })?;
+        options.async_migrations[index]
+            .down(&transaction)
+            .await
+            .map_err(|source| MigrationError::ExecuteCodeMigration {
+                source,
+                name: reversal.name.clone(),
+            })?;
+        transaction
+            .commit()
+            .await
+            .map_err(|source| MigrationError::CommitTransaction { source })?;
+    } else {
+        client
+            .batch_execute(&reversal.sql)
+            .await
+            .map_err(|source| {
+                MigrationError::execute(
+                    source,
+                    &reversal.name,
+                    reversal.path.as_ref(),
+                    &reversal.sql,
+                )
+            })?;
+    }
+    client
+        .execute(
+            history::UPDATE_REVERTED,
+            &[&reversal.namespace, &reversal.name],
+        )
+        .await
+        .map_err(|source| MigrationError::WriteHistory {
+            source,
+            name: reversal.name.clone(),
+        })?;
+
+    Ok(())
+}
+
+/// A migration that was discovered in the migrations directory or registered as code.
+struct MigrationTarget {
+    namespace: String,
+    name: String,
+    /// The path of the migration file, if it was read from a directory.
+    path: Option<PathBuf>,
+    version: u64,
+    sql: String,
+    checksum: Vec<u8>,
+    transaction: bool,
+    down: Option<MigrationFile>,
+    /// The index of the code migration in the registered migrations of the runner's flavour.
+    code: Option<usize>,
+    /// If the target is re-applied whenever its contents change, repeatable targets have no
+    /// version.
+    repeatable: bool,
+    /// If the target is a baseline that covers every migration up to and including its version.
+    baseline: bool,
+    /// The names and checksums of the migrations covered by a baseline, recorded as applied
+    /// alongside the baseline.
+    replaces: Vec<(String, Vec<u8>)>,
+}
+
+impl MigrationTarget {
+    /// The key that identifies the migration in the history.
+    fn key(&self) -> history::HistoryKey {
+        (self.namespace.clone(), self.name.clone())
+    }
+
+    /// The key that orders the targets, repeatable targets come after the versioned targets in name
+    /// order.
+    fn order(&self) -> (bool, u64, &str) {
+        (self.repeatable, self.version, &self.name)
+    }
+
+    /// The names and checksums recorded in the history when the migration is applied.
+    fn recorded(&self) -> impl Iterator<Item = (&str, &[u8])> {
+        core::iter::once((self.name.as_str(), self.checksum.as_slice())).chain(
+            self.replaces
+                .iter()
+                .map(|(name, checksum)| (name.as_str(), checksum.as_slice())),
+        )
+    }
+
+    /// The name of the migration, prefixed by its namespace outside of the default namespace.
+    fn qualified_name(&self) -> String {
+        if self.namespace == DEFAULT_NAMESPACE {
+            self.name.clone()
+        } else {
+            format!("{}/{}", self.namespace, self.name)
+        }
+    }
+}
+
+/// Adds a target for each registered code migration, ordered by version alongside the SQL
+/// migrations.
+///
+/// Code migrations have no contents to compare, so they are recorded with an empty checksum.
+fn with_code_migrations<'a>(
+    mut targets: Vec<MigrationTarget>,
+    names: impl Iterator<Item = &'a str>,
+) -> Result<Vec<MigrationTarget>, MigrationError> {
+    for (index, name) in names.enumerate() {
+        targets.push(MigrationTarget {
+            namespace: DEFAULT_NAMESPACE.to_string(),
+            name: name.to_string(),
+            path: None,
+            version: migration_version(name)?,
+            sql: String::new(),
+            checksum: Vec::new(),
+            transaction: true,
+            down: None,
+            code: Some(index),
+            repeatable: false,
+            baseline: false,
+            replaces: Vec::new(),
+        });
+    }
+    targets.sort_by(|a, b| a.order().cmp(&b.order()));
+    check_duplicate_versions(&targets)?;
+    check_duplicate_names(&targets)?;
+
+    Ok(targets)
+}
+
+/// Errors if two migrations in a namespace share a name, such as repeatable migrations with the same
+/// file name in different directories, as the history is keyed by name.
+fn check_duplicate_names(targets: &[MigrationTarget]) -> Result<(), MigrationError> {
+    let mut keys = HashSet::with_capacity(targets.len());
+    for target in targets {
+        if !keys.insert(target.key()) {
+            return Err(MigrationError::DuplicateName {
+                namespace: target.namespace.clone(),
+                name: target.name.clone(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Errors if two version ordered migrations, or two version ordered baselines, share a version.
+fn check_duplicate_versions(targets: &[MigrationTarget]) -> Result<(), MigrationError> {
+    for baseline in [false, true] {
+        let targets: Vec<&MigrationTarget> = targets
+            .iter()
+            .filter(|target| !target.repeatable && target.baseline == baseline)
+            .collect();
+
+        if let Some(pair) = targets
+            .windows(2)
+            .find(|pair| pair[0].version == pair[1].version)
+        {
+            return Err(MigrationError::DuplicateVersion {
+                version: pair[0].version,
+                names: vec![pair[0].name.clone(), pair[1].name.clone()],
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Prefix of the repeatable migrations that are re-applied whenever their contents change.
+pub const REPEATABLE_PREFIX: &str = "R__";
+
+/// Suffix of the files that revert the migration with the same version.
+pub const DOWN_SUFFIX: &str = ".down.sql";
+
+/// Suffix of the files that replace every migration up to and including their version on a fresh
+/// database.
+pub const BASELINE_SUFFIX: &str = ".baseline.sql";
+
+/// Parses the version of a migration from its `<version>_<description>` name, the description and
+/// any file extensions are ignored so the up and down files of a migration share a version.
+pub fn migration_version(name: &str) -> Result<u64, MigrationError> {
+    let stem = name.split('.').next().unwrap_or(name);
+    let version = stem
+        .split_once('_')
+        .map_or(stem, |(version, _description)| version);
+
+    if version.is_empty() || !version.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Err(MigrationError::MalformedVersion {
+            name: name.to_string(),
+        });
+    }
+
+    version
+        .parse()
+        .map_err(|_| MigrationError::MalformedVersion {
+            name: name.to_string(),
+        })
+}
+
+/// If a migration script should run inside a transaction.
+fn runs_in_transaction(sql: &str) -> bool {
+    !sql.lines().any(|line| line.trim() == NO_TRANSACTION_MARKER)
+}
+
+/// Discovers the migration files in `current_dir()/migrations` in version order, each followed by
+/// its down migration, for tools that inspect the migrations without running them.
+///
+/// Errors if the directory does not exist, or the files are not valid migrations.
+pub fn discover_migrations(
+    migrations_directory: Option<PathBuf>,
+) -> Result<Vec<MigrationFile>, MigrationError> {
+    let source = FilesystemSource {
+        directory: migrations_directory,
+        strict: true,
+    };
+
+    discover_migrations_from_source(&source)
+}
+
+/// Reads the migration files in the directory, or `current_dir()/migrations` if no directory is
+/// given.
+///
+/// A missing directory has no migrations unless `strict` is set, in which case it is an error.
+fn read_migration_files(
+    migrations_directory: Option<PathBuf>,
+    strict: bool,
+) -> Result<Option<Vec<MigrationFile>>, MigrationError> {
+    let path = match migrations_directory {
+        Some(path) => path,
+        None => current_dir()
+            .map_err(|source| MigrationError::CurrentDirectory { source })?
+            .join("migrations"),
+    };
+
+    if !fs::exists(&path).map_err(|source| MigrationError::ReadMigrationDirectory { source })? {
+        if strict {
+            return Err(MigrationError::MissingMigrationDirectory { path });
+        }
+        return Ok(None);
+    }
+
+    let mut paths = Vec::new();
+    collect_migration_files(&path, &mut paths)?;
+
+    let files = paths
+        .into_iter()
+        .map(|path| {
+            let name = path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned();
+            let sql = fs::read_to_string(&path)
+                .map_err(|source| MigrationError::ReadMigrationFile { source })?;
+            Ok(MigrationFile {
+                name,
+                path: Some(path),
+                sql,
+            })
+        })
+        .collect::<Result<_, MigrationError>>()?;
+
+    Ok(Some(files))
+}
+
+/// Collects the paths of the `*.sql` files in the directory and its subdirectories.
+fn collect_migration_files(
+    directory: &Path,
+    paths: &mut Vec<PathBuf>,
+) -> Result<(), MigrationError> {
+    let mut entries = fs::read_dir(directory)
+        .map_err(|source| MigrationError::ReadMigrationDirectory { source })?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|source| MigrationError::ReadMigrationDirectory { source })?;
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            collect_migration_files(&path, paths)?;
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == OsStr::new("sql"))
+        {
+            paths.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// A migration file that was read from a directory or embedded into the binary.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct MigrationFile {
+    /// The file name of the migration.
+    pub name: String,
+    /// The path of the migration, if it was read from a directory.
+    pub path: Option<PathBuf>,
+    /// The contents of the migration, before any variables are substituted.
+    pub sql: String,
+}
+
+impl MigrationFile {
+    /// Creates a migration file with the file name and contents that was not read from a
+    /// directory.
+    pub fn new(name: impl Into<String>, sql: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            path: None,
+            sql: sql.into(),
+        }
+    }
+}
+
+/// Converts the migration files into version ordered targets, pairing each down migration with the
+/// migration it reverts.
+fn migration_targets(files: Vec<MigrationFile>) -> Result<Vec<MigrationTarget>, MigrationError> {
+    let mut targets: Vec<MigrationTarget> = Vec::new();
+    let mut down_migrations: Vec<MigrationFile> = Vec::new();
+    for file in files {
+        if file.name.ends_with(DOWN_SUFFIX) {
+            down_migrations.push(file);
+            continue;
+        }
+        let MigrationFile { name, path, sql } = file;
+
+        let repeatable = name.starts_with(REPEATABLE_PREFIX);
+        let name_is_baseline = !repeatable && name.ends_with(BASELINE_SUFFIX);
+        targets.push(MigrationTarget {
+            namespace: DEFAULT_NAMESPACE.to_string(),
+            path,
+            version: if repeatable {
+                0
+            } else {
+                migration_version(&name)?
+            },
+            name,
+            checksum: history::checksum(&sql),
+            transaction: runs_in_transaction(&sql),
+            sql,
+            down: None,
+            code: None,
+            repeatable,
+            baseline: name_is_baseline,
+            replaces: Vec::new(),
+        });
+    }
+
+    targets.sort_by(|a, b| a.order().cmp(&b.order()));
+    check_duplicate_versions(&targets)?;
+    check_duplicate_names(&targets)?;
+
+    for down in down_migrations {
+        let version = migration_version(&down.name)?;
+        let Some(target) = targets
+            .iter_mut()
+            .find(|target| !target.repeatable && !target.baseline && target.version == version)
+        else {
+            return Err(MigrationError::UnpairedDownMigration { name: down.name });
+        };
+        if target.down.is_some() {
+            return Err(MigrationError::DuplicateVersion {
+                version,
+                names: vec![target.name.clone(), down.name],
+            });
+        }
+        target.down = Some(down);
+    }
+
+    Ok(targets)
+}
+
+impl MigrationError {
+    /// Creates an [`ExecuteMigration`][Self::ExecuteMigration] error, locating the failing
+    /// statement in the executed SQL.
+    fn execute(source: postgres::Error, name: &str, path: Option<&PathBuf>, sql: &str) -> Self {
+        Self::ExecuteMigration {
+            name: name.to_string(),
+            path: path.cloned(),
+            code: source.code().cloned(),
+            location: location::locate(sql, &source).map(Box::new),
+            source,
+        }
+    }
+}
+
+/// Error variants for migrating a database.
+#[derive(Debug)]
+#[non_exhaustive]
+#[allow(missing_docs)]
+pub enum MigrationError {
+    #[non_exhaustive]
+    CurrentDirectory { source: io::Error },
+
+    #[non_exhaustive]
+    MissingMigrationDirectory { path: PathBuf },
+
+    #[non_exhaustive]
+    ReadMigrationDirectory { source: io::Error },
+
+    #[non_exhaustive]
+    ReadMigrationFile { source: io::Error },
+
+    #[non_exhaustive]
+    ReadMigrationSource { source: MigrationSourceError },
+
+    #[non_exhaustive]
+    ExecuteMigration {
+        source: postgres::Error,
+        name: String,
+        path: Option<PathBuf>,
+        code: Option<SqlState>,
+        location: Option<Box<MigrationLocation>>,
+    },
+
+    #[non_exhaustive]
+    MalformedVersion { name: String },
+
+    #[non_exhaustive]
+    DuplicateVersion { version: u64, names: Vec<String> },
+
+    #[non_exhaustive]
+    DuplicateName { namespace: String, name: String },
+
+    #[non_exhaustive]
+    UnpairedDownMigration { name: String },
+
+    #[non_exhaustive]
+    MissingDownMigration { name: String },
+
+    #[non_exhaustive]
+    ExecuteCodeMigration {
+        source: CodeMigrationError,
+        name: String,
+    },
+
+    #[non_exhaustive]
+    ChecksumMismatch { name: String },
+
+    #[non_exhaustive]
+    DuplicateNamespace { namespace: String },
+
+    #[non_exhaustive]
+    UnknownDependency {
+        namespace: String,
+        dependency: String,
+    },
+
+    #[non_exhaustive]
+    DependencyCycle { namespaces: Vec<String> },
+
+    #[non_exhaustive]
+    TransactionDisabled { name: String },
+
+    #[non_exhaustive]
+    MissingVariable { name: String, variable: String },
+
+    #[non_exhaustive]
+    AcquireLock { source: postgres::Error },
+
+    #[non_exhaustive]
+    ReleaseLock { source: postgres::Error },
+
+    #[non_exhaustive]
+    BeginTransaction { source: postgres::Error },
+
+    #[non_exhaustive]
+    CommitTransaction { source: postgres::Error },
+
+    #[non_exhaustive]
+    RollbackTransaction { source: postgres::Error },
+
+    #[non_exhaustive]
+    CreateHistory { source: postgres::Error },
+
+    #[non_exhaustive]
+    ReadHistory { source: postgres::Error },
+
+    #[non_exhaustive]
+    WriteHistory {
+        source: postgres::Error,
+        name: String,
+    },
+}
+impl core::fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match &self {
+            Self::CurrentDirectory { .. } => write!(f, "could not get the current directory"),
+            Self::MissingMigrationDirectory { path } => {
+                write!(f, "migration directory `{}` does not exist", path.display())
+            }
+            Self::ReadMigrationDirectory { .. } => write!(f, "could not read migration directory"),
+            Self::ReadMigrationFile { .. } => write!(f, "could not read a migration file"),
+            Self::ReadMigrationSource { .. } => write!(f, "could not load the migrations"),
+            Self::ExecuteMigration {
+                name,
+                path,
+                code,
+                location,
+                ..
+            } => {
+                match path {
+                    Some(path) => write!(f, "migration `{}` failed to execute", path.display())?,
+                    None => write!(f, "migration `{name}` failed to execute")?,
+                }
+                if let Some(code) = code {
+                    write!(f, " with code `{}`", code.code())?;
+                }
+                if let Some(location) = location {
+                    write!(
+                        f,
+                        " at line {}, column {}\n{location}",
+                        location.line, location.column
+                    )?;
+                }
+                Ok(())
+            }
+            Self::MalformedVersion { name } => write!(
+                f,
+                "migration `{name}` is not named `<version>_<description>.sql`"
+            ),
+            Self::DuplicateVersion { version, names } => write!(
+                f,
+                "migrations `{}` share the version `{version}`",
+                names.join("`, `")
+            ),
+            Self::DuplicateName { namespace, name } => write!(
+                f,
+                "multiple migrations in the namespace `{namespace}` are named `{name}`"
+            ),
+            Self::UnpairedDownMigration { name } => {
+                write!(f, "down migration `{name}` has no matching up migration")
+            }
+            Self::MissingDownMigration { name } => {
+                write!(
+                    f,
+                    "applied migration `{name}` has no down migration to revert it"
+                )
+            }
+            Self::ExecuteCodeMigration { name, .. } => {
+                write!(f, "code migration `{name}` failed to execute")
+            }
+            Self::ChecksumMismatch { name } => write!(
+                f,
+                "applied migration `{name}` has been modified since it was applied"
+            ),
+            Self::DuplicateNamespace { namespace } => {
+                write!(f, "multiple migration sets use the namespace `{namespace}`")
+            }
+            Self::UnknownDependency {
+                namespace,
+                dependency,
+            } => write!(
+                f,
+                "migration set `{namespace}` depends on unknown migration set `{dependency}`"
+            ),
+            Self::DependencyCycle { namespaces } => write!(
+                f,
+                "migration sets `{}` depend on each other",
+                namespaces.join("`, `")
+            ),
+            Self::TransactionDisabled { name } => write!(
+                f,
+                "migration `{name}` cannot run inside a transaction spanning the whole batch"
+            ),
+            Self::MissingVariable { name, variable } => write!(
+                f,
+                "migration `{name}` references the variable `{variable}` that has no value"
+            ),
+            Self::AcquireLock { .. } => write!(f, "could not acquire the migration lock"),
+            Self::ReleaseLock { .. } => write!(f, "could not release the migration lock"),
+            Self::BeginTransaction { .. } => write!(f, "could not begin a migration transaction"),
+            Self::CommitTransaction { .. } => write!(f, "could not commit a migration transaction"),
+            Self::RollbackTransaction { .. } => {
+                write!(f, "could not roll back a migration transaction")
+            }
+            Self::CreateHistory { .. } => write!(f, "could not create the migration history table"),
+            Self::ReadHistory { .. } => write!(f, "could not read the migration history"),
+            Self::WriteHistory { name, .. } => {
+                write!(
+                    f,
+                    "could not record migration `{name}` in the migration history"
+                )
+            }
+        }
+    }
+}
+impl core::error::Error for MigrationError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match &self {
+            Self::CurrentDirectory { source, .. } => Some(source),
+            Self::MissingMigrationDirectory { .. } => None,
+            Self::ReadMigrationDirectory { source, .. } => Some(source),
+            Self::ReadMigrationFile { source, .. } => Some(source),
+            Self::ReadMigrationSource { source, .. } => Some(source.as_ref()),
+            Self::ExecuteMigration { source, .. } => Some(source),
+            Self::MalformedVersion { .. } => None,
+            Self::DuplicateVersion { .. } => None,
+            Self::DuplicateName { .. } => None,
+            Self::UnpairedDownMigration { .. } => None,
+            Self::MissingDownMigration { .. } => None,
+            Self::ExecuteCodeMigration { source, .. } => Some(source.as_ref()),
+            Self::ChecksumMismatch { .. } => None,
+            Self::DuplicateNamespace { .. } => None,
+            Self::UnknownDependency { .. } => None,
+            Self::DependencyCycle { .. } => None,
+            Self::TransactionDisabled { .. } => None,
+            Self::MissingVariable { .. } => None,
+            Self::AcquireLock { source, .. } => Some(source),
+            Self::ReleaseLock { source, .. } => Some(source),
+            Self::BeginTransaction { source, .. } => Some(source),
+            Self::CommitTransaction { source, .. } => Some(source),
+            Self::RollbackTransaction { source, .. } => Some(source),
+            Self::CreateHistory { source, .. } => Some(source),
+            Self::ReadHistory { source, .. } => Some(source),
+            Self::WriteHistory { source, .. } => Some(source),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MigrationError, MigrationFile, migration_targets, migration_version};
+
+    fn names(files: &[&str]) -> Result<Vec<String>, MigrationError> {
+        let files = files
+            .iter()
+            .map(|name| MigrationFile::new(*name, "SELECT 1;"))
+            .collect();
+
+        Ok(migration_targets(files)?
+            .into_iter()
+            .map(|target| target.name)
+            .collect())
+    }
+
+    #[test]
+    fn parses_versions() {
+        assert_eq!(migration_version("2_users.sql").unwrap(), 2);
+        assert_eq!(migration_version("10_posts.up.sql").unwrap(), 10);
+        assert_eq!(migration_version("001.sql").unwrap(), 1);
+        assert_eq!(migration_version("7_backfill").unwrap(), 7);
+    }
+
+    #[test]
+    fn rejects_malformed_versions() {
+        for name in [
+            "_users.sql",
+            "users.sql",
+            "1a_users.sql",
+            "R__views.sql",
+            "",
+        ] {
+            assert!(
+                matches!(
+                    migration_version(name),
+                    Err(MigrationError::MalformedVersion { .. })
+                ),
+                "`{name}` should be malformed"
+            );
+        }
+    }
+
+    #[test]
+    fn orders_by_numeric_version() {
+        assert_eq!(
+            names(&["10_posts.sql", "2_users.sql", "1_init.sql"]).unwrap(),
+            ["1_init.sql", "2_users.sql", "10_posts.sql"]
+        );
+    }
+
+    #[test]
+    fn rejects_duplicate_names() {
+        let files = vec![
+            MigrationFile::new(
+                "R__users.sql",
+                "CREATE OR REPLACE VIEW users_view AS SELECT 1;",
+            ),
+            MigrationFile::new("R__users.sql", "CREATE OR REPLACE FUNCTION users() ..."),
+        ];
+
+        assert!(matches!(
+            migration_targets(files),
+            Err(MigrationError::DuplicateName { name, .. }) if name == "R__users.sql"
+        ));
+    }
+
+    #[test]
+    fn rejects_duplicate_versions() {
+        let result = names(&["01_users.sql", "1_posts.sql"]);
+        assert!(matches!(
+            result,
+            Err(MigrationError::DuplicateVersion { version: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_empty_version() {
+        assert!(matches!(
+            names(&["1_init.sql", "_users.sql"]),
+            Err(MigrationError::MalformedVersion { .. })
+        ));
+    }
+
+    #[test]
+    fn orders_repeatable_migrations_by_name() {
+        assert_eq!(
+            names(&["R__b.sql", "R__a.sql", "2_posts.sql", "1_init.sql"]).unwrap(),
+            ["1_init.sql", "2_posts.sql", "R__a.sql", "R__b.sql"]
+        );
+    }
+
+    #[test]
+    fn repeatable_migrations_have_no_version() {
+        assert_eq!(
+            names(&["R__views.sql", "R__functions.sql", "1_init.sql"]).unwrap()[0],
+            "1_init.sql"
+        );
+    }
+}
@@ -0,0 +1,32 @@
+//! Callbacks that report the progress of a migration run.
+//!
+
+use core::time::Duration;
+
+/// The outcome of a migration that was applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MigrationReport {
+    /// The name of the migration, prefixed by its namespace outside of the default namespace.
+    pub name: String,
+    /// How long the migration took to execute.
+    pub duration: Duration,
+    /// The number of rows affected by the statements of the migration, `None` for code
+    /// migrations.
+    pub rows_affected: Option<u64>,
+}
+
+/// Receives the progress of a migration run, such as to report each migration as it is applied.
+///
+/// The callbacks are called for the pending migrations only, and are also called for dry runs.
+pub trait MigrationObserver: Send + Sync {
+    /// Called before a migration is executed.
+    fn before_migration(&self, name: &str) {
+        let _ = name;
+    }
+
+    /// Called after a migration has been executed and recorded in the history.
+    fn after_migration(&self, report: &MigrationReport) {
+        let _ = report;
+    }
+}
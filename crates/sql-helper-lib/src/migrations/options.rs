@@ -0,0 +1,129 @@
+//! Options for running migrations.
+//!
+
+use core::time::Duration;
+use std::{collections::HashMap, sync::Arc};
+
+#[cfg(feature = "async")]
+use crate::migrations::AsyncMigration;
+use crate::migrations::{Migration, MigrationObserver};
+
+/// The default key of the advisory lock that guards the migrations, the bytes of `sql-help`.
+pub const DEFAULT_LOCK_KEY: i64 = 0x7371_6c2d_6865_6c70;
+
+/// Options that control how migrations are run.
+#[derive(Clone)]
+pub struct MigrationOptions {
+    /// Run every pending migration inside a single transaction, if any migration fails then none
+    /// of the migrations are applied.
+    ///
+    /// Migrations containing the [`NO_TRANSACTION_MARKER`][crate::NO_TRANSACTION_MARKER] are
+    /// rejected in this mode.
+    pub single_transaction: bool,
+
+    /// Run every pending migration inside a single transaction that is always rolled back, to
+    /// validate the migrations against a database without modifying it.
+    ///
+    /// Migrations containing the [`NO_TRANSACTION_MARKER`][crate::NO_TRANSACTION_MARKER] are
+    /// rejected in this mode.
+    pub dry_run: bool,
+
+    /// Error if the migrations directory does not exist, instead of treating it as having no
+    /// migrations.
+    ///
+    /// Reading the status, repairing, and rolling back always error for a missing directory.
+    pub strict: bool,
+
+    /// The key of the advisory lock that is held while the migrations run.
+    pub lock_key: i64,
+
+    /// How long to wait for the advisory lock before giving up, `None` waits indefinitely.
+    pub lock_timeout: Option<Duration>,
+
+    /// Values that replace the `${name}` placeholders in the migration files before they are
+    /// executed. Checksums are calculated from the files before substitution.
+    ///
+    /// Referencing a variable without a value fails the migration.
+    ///
+    /// The history records each migration by its namespace and name, not by its variables, so
+    /// applying the same migrations again with different variables applies nothing. To apply the
+    /// same migrations once per tenant, run them as a [`MigrationSet`][crate::MigrationSet] with a
+    /// namespace for each tenant:
+    ///
+    /// ```no_run
+    /// use ts_sql_helper_lib::{
+    ///     MigrationError, MigrationOptions, MigrationSet, perform_migration_sets, postgres::Client,
+    /// };
+    ///
+    /// fn migrate_tenants(client: &mut Client) -> Result<(), MigrationError> {
+    ///     for tenant in ["tenant1", "tenant2"] {
+    ///         let options = MigrationOptions {
+    ///             variables: [("schema".to_string(), tenant.to_string())].into(),
+    ///             ..Default::default()
+    ///         };
+    ///         let set = MigrationSet::directory(tenant, "migrations/tenant");
+    ///         perform_migration_sets(client, &[set], &options)?;
+    ///     }
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub variables: HashMap<String, String>,
+
+    /// Code migrations that are run by the sync runners alongside the SQL migrations.
+    pub migrations: Vec<Arc<dyn Migration>>,
+
+    #[cfg(feature = "async")]
+    /// Code migrations that are run by the async runners alongside the SQL migrations.
+    pub async_migrations: Vec<Arc<dyn AsyncMigration>>,
+
+    /// Receives the progress of each migration as it is applied.
+    pub observer: Option<Arc<dyn MigrationObserver>>,
+}
+impl Default for MigrationOptions {
+    fn default() -> Self {
+        Self {
+            single_transaction: false,
+            dry_run: false,
+            strict: false,
+            lock_key: DEFAULT_LOCK_KEY,
+            lock_timeout: None,
+            variables: HashMap::new(),
+            migrations: Vec::new(),
+            #[cfg(feature = "async")]
+            async_migrations: Vec::new(),
+            observer: None,
+        }
+    }
+}
+impl core::fmt::Debug for MigrationOptions {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut debug = f.debug_struct("MigrationOptions");
+        debug
+            .field("single_transaction", &self.single_transaction)
+            .field("dry_run", &self.dry_run)
+            .field("strict", &self.strict)
+            .field("lock_key", &self.lock_key)
+            .field("lock_timeout", &self.lock_timeout)
+            .field("variables", &self.variables)
+            .field(
+                "migrations",
+                &self
+                    .migrations
+                    .iter()
+                    .map(|migration| migration.name())
+                    .collect::<Vec<_>>(),
+            );
+        #[cfg(feature = "async")]
+        debug.field(
+            "async_migrations",
+            &self
+                .async_migrations
+                .iter()
+                .map(|migration| migration.name())
+                .collect::<Vec<_>>(),
+        );
+        debug.field("observer", &self.observer.is_some());
+        debug.finish()
+    }
+}
@@ -0,0 +1,313 @@
+//! Named sets of migrations that are tracked under their own namespace.
+//!
+
+use std::{path::PathBuf, sync::Arc};
+
+#[cfg(feature = "async")]
+use crate::migrations::apply_pending_migrations_async;
+use crate::migrations::{
+    DEFAULT_NAMESPACE, EmbeddedMigration, EmbeddedSource, FilesystemSource, MigrationError,
+    MigrationOptions, MigrationSource, MigrationTarget, apply_pending_migrations, lock,
+    source::source_targets, with_code_migrations,
+};
+
+/// Where the migrations of a [`MigrationSet`] are read from.
+#[derive(Clone)]
+#[non_exhaustive]
+pub enum MigrationSetSource {
+    /// A directory that is searched recursively for `*.sql` files.
+    Directory(PathBuf),
+    /// Migrations that were embedded into the binary, usually by the `embed_migrations!` macro.
+    Embedded(&'static [EmbeddedMigration]),
+    /// Migrations loaded from any other source.
+    Source(Arc<dyn MigrationSource>),
+}
+impl core::fmt::Debug for MigrationSetSource {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Directory(directory) => f.debug_tuple("Directory").field(directory).finish(),
+            Self::Embedded(migrations) => f.debug_tuple("Embedded").field(migrations).finish(),
+            Self::Source(_) => f.debug_tuple("Source").finish_non_exhaustive(),
+        }
+    }
+}
+
+/// A group of migrations that is recorded under its own namespace in the history, so the versions
+/// of different sets are independent of each other.
+#[derive(Debug, Clone)]
+pub struct MigrationSet {
+    /// The namespace the migrations are recorded under.
+    pub namespace: String,
+    /// Where the migrations are read from.
+    pub source: MigrationSetSource,
+    /// The namespaces of the sets that must be migrated before this set.
+    pub depends_on: Vec<String>,
+}
+
+impl MigrationSet {
+    /// Creates a set from a directory of migrations.
+    pub fn directory(namespace: impl Into<String>, directory: impl Into<PathBuf>) -> Self {
+        Self {
+            namespace: namespace.into(),
+            source: MigrationSetSource::Directory(directory.into()),
+            depends_on: Vec::new(),
+        }
+    }
+
+    /// Creates a set from embedded migrations.
+    pub fn embedded(
+        namespace: impl Into<String>,
+        migrations: &'static [EmbeddedMigration],
+    ) -> Self {
+        Self {
+            namespace: namespace.into(),
+            source: MigrationSetSource::Embedded(migrations),
+            depends_on: Vec::new(),
+        }
+    }
+
+    /// Creates a set from a migration source.
+    pub fn source(namespace: impl Into<String>, source: Arc<dyn MigrationSource>) -> Self {
+        Self {
+            namespace: namespace.into(),
+            source: MigrationSetSource::Source(source),
+            depends_on: Vec::new(),
+        }
+    }
+
+    /// Requires the set with the namespace to be migrated before this set.
+    pub fn depends_on(mut self, namespace: impl Into<String>) -> Self {
+        self.depends_on.push(namespace.into());
+        self
+    }
+
+    /// Reads the version ordered targets of the set, recorded under the set's namespace.
+    fn targets(&self, strict: bool) -> Result<Vec<MigrationTarget>, MigrationError> {
+        let mut targets = match &self.source {
+            MigrationSetSource::Directory(directory) => source_targets(&FilesystemSource {
+                directory: Some(directory.clone()),
+                strict,
+            })?,
+            MigrationSetSource::Embedded(migrations) => {
+                source_targets(&EmbeddedSource::new(migrations))?
+            }
+            MigrationSetSource::Source(source) => source_targets(source.as_ref())?,
+        };
+        for target in &mut targets {
+            target.namespace.clone_from(&self.namespace);
+        }
+
+        Ok(targets)
+    }
+}
+
+/// Orders the sets so that each set comes after the sets it depends on, otherwise the given order
+/// is kept.
+fn dependency_order(sets: &[MigrationSet]) -> Result<Vec<&MigrationSet>, MigrationError> {
+    for (index, set) in sets.iter().enumerate() {
+        if sets[..index]
+            .iter()
+            .any(|other| other.namespace == set.namespace)
+        {
+            return Err(MigrationError::DuplicateNamespace {
+                namespace: set.namespace.clone(),
+            });
+        }
+
+        if let Some(dependency) = set
+            .depends_on
+            .iter()
+            .find(|dependency| !sets.iter().any(|other| &other.namespace == *dependency))
+        {
+            return Err(MigrationError::UnknownDependency {
+                namespace: set.namespace.clone(),
+                dependency: dependency.clone(),
+            });
+        }
+    }
+
+    let mut ordered: Vec<&MigrationSet> = Vec::with_capacity(sets.len());
+    while ordered.len() < sets.len() {
+        let next = sets.iter().find(|set| {
+            !ordered.iter().any(|done| done.namespace == set.namespace)
+                && set
+                    .depends_on
+                    .iter()
+                    .all(|dependency| ordered.iter().any(|done| &done.namespace == dependency))
+        });
+
+        let Some(next) = next else {
+            return Err(MigrationError::DependencyCycle {
+                namespaces: sets
+                    .iter()
+                    .filter(|set| !ordered.iter().any(|done| done.namespace == set.namespace))
+                    .map(|set| set.namespace.clone())
+                    .collect(),
+            });
+        };
+        ordered.push(next);
+    }
+
+    Ok(ordered)
+}
+
+/// Collects the targets of every set in dependency order.
+///
+/// The code migrations are recorded in the default namespace, they run alongside the set using the
+/// default namespace, or before every set if there is no such set.
+fn set_targets<'a>(
+    sets: &[MigrationSet],
+    strict: bool,
+    code_migrations: impl Iterator<Item = &'a str>,
+) -> Result<Vec<MigrationTarget>, MigrationError> {
+    let ordered = dependency_order(sets)?;
+
+    let mut targets = Vec::new();
+    let mut code_migrations = Some(code_migrations);
+    if !ordered.iter().any(|set| set.namespace == DEFAULT_NAMESPACE)
+        && let Some(code_migrations) = code_migrations.take()
+    {
+        targets = with_code_migrations(targets, code_migrations)?;
+    }
+
+    for set in ordered {
+        let mut set_targets = set.targets(strict)?;
+        if set.namespace == DEFAULT_NAMESPACE
+            && let Some(code_migrations) = code_migrations.take()
+        {
+            set_targets = with_code_migrations(set_targets, code_migrations)?;
+        }
+        targets.extend(set_targets);
+    }
+
+    Ok(targets)
+}
+
+/// Runs the migrations of each set on the client using the options, returns the names of the
+/// applied migrations. Sets are migrated after the sets they depend on, and the migrations of each
+/// set are executed in version order. This has the same semantics as
+/// [`perform_migrations_with_options`][crate::perform_migrations_with_options].
+///
+/// Applied migrations outside of the [`DEFAULT_NAMESPACE`] are named `<namespace>/<name>`.
+pub fn perform_migration_sets(
+    client: &mut postgres::Client,
+    sets: &[MigrationSet],
+    options: &MigrationOptions,
+) -> Result<Vec<String>, MigrationError> {
+    let targets = set_targets(
+        sets,
+        options.strict,
+        options.migrations.iter().map(|migration| migration.name()),
+    )?;
+    if targets.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    lock::acquire(client, options)?;
+    let result = apply_pending_migrations(client, targets, options);
+    let released = lock::release(client, options);
+
+    let applied = result?;
+    released?;
+
+    Ok(applied)
+}
+
+#[cfg(feature = "async")]
+/// Runs the migrations of each set on the client using the options, returns the names of the
+/// applied migrations. This has the same semantics as [`perform_migration_sets`].
+pub async fn perform_migration_sets_async(
+    client: &mut tokio_postgres::Client,
+    sets: &[MigrationSet],
+    options: &MigrationOptions,
+) -> Result<Vec<String>, MigrationError> {
+    let targets = set_targets(
+        sets,
+        options.strict,
+        options
+            .async_migrations
+            .iter()
+            .map(|migration| migration.name()),
+    )?;
+    if targets.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    lock::acquire_async(client, options).await?;
+    let result = apply_pending_migrations_async(client, targets, options).await;
+    let released = lock::release_async(client, options).await;
+
+    let applied = result?;
+    released?;
+
+    Ok(applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MigrationError, MigrationSet, dependency_order};
+
+    fn set(namespace: &str) -> MigrationSet {
+        MigrationSet::embedded(namespace, &[])
+    }
+
+    fn namespaces(sets: &[MigrationSet]) -> Result<Vec<&str>, MigrationError> {
+        Ok(dependency_order(sets)?
+            .into_iter()
+            .map(|set| set.namespace.as_str())
+            .collect())
+    }
+
+    #[test]
+    fn keeps_order_without_dependencies() {
+        let sets = [set("users"), set("billing"), set("audit")];
+
+        assert_eq!(namespaces(&sets).unwrap(), ["users", "billing", "audit"]);
+    }
+
+    #[test]
+    fn orders_sets_after_their_dependencies() {
+        let sets = [
+            set("billing").depends_on("users"),
+            set("audit").depends_on("billing"),
+            set("users"),
+        ];
+
+        assert_eq!(namespaces(&sets).unwrap(), ["users", "billing", "audit"]);
+    }
+
+    #[test]
+    fn rejects_duplicate_namespaces() {
+        let sets = [set("users"), set("users")];
+
+        assert!(matches!(
+            namespaces(&sets),
+            Err(MigrationError::DuplicateNamespace { namespace }) if namespace == "users"
+        ));
+    }
+
+    #[test]
+    fn rejects_unknown_dependencies() {
+        let sets = [set("billing").depends_on("users")];
+
+        assert!(matches!(
+            namespaces(&sets),
+            Err(MigrationError::UnknownDependency { namespace, dependency })
+                if namespace == "billing" && dependency == "users"
+        ));
+    }
+
+    #[test]
+    fn rejects_dependency_cycles() {
+        let sets = [
+            set("users"),
+            set("billing").depends_on("audit"),
+            set("audit").depends_on("billing"),
+        ];
+
+        assert!(matches!(
+            namespaces(&sets),
+            Err(MigrationError::DependencyCycle { namespaces }) if namespaces == ["billing", "audit"]
+        ));
+    }
+}
@@ -0,0 +1,288 @@
+//! Sources that the migration files are loaded from.
+//!
+
+use std::path::PathBuf;
+
+use crate::migrations::{
+    EmbeddedMigration, MigrationError, MigrationFile, MigrationOptions, MigrationTarget,
+    apply_pending_migrations, lock, migration_targets, read_migration_files, repair_targets,
+    rollback_targets, with_code_migrations,
+};
+#[cfg(feature = "async")]
+use crate::migrations::{
+    apply_pending_migrations_async, repair_targets_async, rollback_targets_async,
+};
+
+/// The error returned by a migration source that failed to load its migrations.
+pub type MigrationSourceError = Box<dyn core::error::Error + Send + Sync>;
+
+/// Loads the migration files that are used by the `*_from_source` functions, such as
+/// [`perform_migrations_from_source`][crate::perform_migrations_from_source].
+///
+/// The files follow the same naming as the files in a migrations directory, and may be returned in
+/// any order.
+pub trait MigrationSource: Send + Sync {
+    /// Loads the migration files.
+    fn migrations(&self) -> Result<Vec<MigrationFile>, MigrationSourceError>;
+}
+
+/// Loads the `*.sql` files in a directory and its subdirectories.
+#[derive(Debug, Clone)]
+pub struct FilesystemSource {
+    /// The directory containing the migrations, `None` uses `current_dir()/migrations`.
+    pub directory: Option<PathBuf>,
+    /// Error if the directory does not exist, instead of treating it as having no migrations.
+    pub strict: bool,
+}
+
+impl FilesystemSource {
+    /// Creates a source for the directory, `None` uses `current_dir()/migrations`.
+    pub fn new(directory: Option<PathBuf>) -> Self {
+        Self {
+            directory,
+            strict: false,
+        }
+    }
+}
+
+impl MigrationSource for FilesystemSource {
+    fn migrations(&self) -> Result<Vec<MigrationFile>, MigrationSourceError> {
+        Ok(read_migration_files(self.directory.clone(), self.strict)?.unwrap_or_default())
+    }
+}
+
+/// Loads migrations that were embedded into the binary, usually by the `embed_migrations!` macro.
+#[derive(Debug, Clone, Copy)]
+pub struct EmbeddedSource<'a> {
+    /// The embedded migrations.
+    pub migrations: &'a [EmbeddedMigration],
+}
+
+impl<'a> EmbeddedSource<'a> {
+    /// Creates a source for the embedded migrations.
+    pub fn new(migrations: &'a [EmbeddedMigration]) -> Self {
+        Self { migrations }
+    }
+}
+
+impl MigrationSource for EmbeddedSource<'_> {
+    fn migrations(&self) -> Result<Vec<MigrationFile>, MigrationSourceError> {
+        Ok(self
+            .migrations
+            .iter()
+            .map(|migration| MigrationFile::new(migration.name, migration.sql))
+            .collect())
+    }
+}
+
+/// Holds migrations that were constructed at runtime, such as by tests.
+#[derive(Debug, Clone, Default)]
+pub struct InMemorySource {
+    /// The migration files.
+    pub files: Vec<MigrationFile>,
+}
+
+impl InMemorySource {
+    /// Creates a source without any migrations.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a migration with the file name and contents.
+    pub fn with_migration(mut self, name: impl Into<String>, sql: impl Into<String>) -> Self {
+        self.files.push(MigrationFile::new(name, sql));
+        self
+    }
+}
+
+impl MigrationSource for InMemorySource {
+    fn migrations(&self) -> Result<Vec<MigrationFile>, MigrationSourceError> {
+        Ok(self.files.clone())
+    }
+}
+
+/// Loads the version ordered targets of the source.
+pub fn source_targets(
+    source: &dyn MigrationSource,
+) -> Result<Vec<MigrationTarget>, MigrationError> {
+    // The built in sources return a boxed `MigrationError`, which is passed through as is.
+    let files = source
+        .migrations()
+        .map_err(|error| match error.downcast::<MigrationError>() {
+            Ok(error) => *error,
+            Err(source) => MigrationError::ReadMigrationSource { source },
+        })?;
+
+    migration_targets(files)
+}
+
+/// Runs the migrations of the source on the client using the options, returns the names of the
+/// applied migrations. This has the same semantics as
+/// [`perform_migrations_with_options`][crate::perform_migrations_with_options].
+pub fn perform_migrations_from_source(
+    client: &mut postgres::Client,
+    source: &dyn MigrationSource,
+    options: &MigrationOptions,
+) -> Result<Vec<String>, MigrationError> {
+    let targets = with_code_migrations(
+        source_targets(source)?,
+        options.migrations.iter().map(|migration| migration.name()),
+    )?;
+    if targets.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    lock::acquire(client, options)?;
+    let result = apply_pending_migrations(client, targets, options);
+    let released = lock::release(client, options);
+
+    let applied = result?;
+    released?;
+
+    Ok(applied)
+}
+
+#[cfg(feature = "async")]
+/// Runs the migrations of the source on the client using the options, returns the names of the
+/// applied migrations. This has the same semantics as
+/// [`perform_migrations_with_options_async`][crate::perform_migrations_with_options_async].
+pub async fn perform_migrations_from_source_async(
+    client: &mut tokio_postgres::Client,
+    source: &dyn MigrationSource,
+    options: &MigrationOptions,
+) -> Result<Vec<String>, MigrationError> {
+    let targets = with_code_migrations(
+        source_targets(source)?,
+        options
+            .async_migrations
+            .iter()
+            .map(|migration| migration.name()),
+    )?;
+    if targets.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    lock::acquire_async(client, options).await?;
+    let result = apply_pending_migrations_async(client, targets, options).await;
+    let released = lock::release_async(client, options).await;
+
+    let applied = result?;
+    released?;
+
+    Ok(applied)
+}
+
+/// Accepts the current contents of every applied migration of the source by replacing the recorded
+/// checksums, returns the names of the repaired migrations. This has the same semantics as
+/// [`repair_migrations_with_options`][crate::repair_migrations_with_options].
+pub fn repair_migrations_from_source(
+    client: &mut postgres::Client,
+    source: &dyn MigrationSource,
+    options: &MigrationOptions,
+) -> Result<Vec<String>, MigrationError> {
+    let targets = source_targets(source)?;
+
+    lock::acquire(client, options)?;
+    let result = repair_targets(client, targets);
+    let released = lock::release(client, options);
+
+    let repaired = result?;
+    released?;
+
+    Ok(repaired)
+}
+
+#[cfg(feature = "async")]
+/// Accepts the current contents of every applied migration of the source by replacing the recorded
+/// checksums, returns the names of the repaired migrations. This has the same semantics as
+/// [`repair_migrations_with_options_async`][crate::repair_migrations_with_options_async].
+pub async fn repair_migrations_from_source_async(
+    client: &mut tokio_postgres::Client,
+    source: &dyn MigrationSource,
+    options: &MigrationOptions,
+) -> Result<Vec<String>, MigrationError> {
+    let targets = source_targets(source)?;
+
+    lock::acquire_async(client, options).await?;
+    let result = repair_targets_async(client, targets).await;
+    let released = lock::release_async(client, options).await;
+
+    let repaired = result?;
+    released?;
+
+    Ok(repaired)
+}
+
+/// Reverts the applied migrations of the source with a version after the target in reverse version
+/// order, returns the names of the reverted migrations. This has the same semantics as
+/// [`rollback_migrations_with_options`][crate::rollback_migrations_with_options].
+pub fn rollback_migrations_from_source(
+    client: &mut postgres::Client,
+    source: &dyn MigrationSource,
+    target: Option<u64>,
+    options: &MigrationOptions,
+) -> Result<Vec<String>, MigrationError> {
+    let targets = with_code_migrations(
+        source_targets(source)?,
+        options.migrations.iter().map(|migration| migration.name()),
+    )?;
+
+    lock::acquire(client, options)?;
+    let result = rollback_targets(client, targets, target, options);
+    let released = lock::release(client, options);
+
+    let reverted = result?;
+    released?;
+
+    Ok(reverted)
+}
+
+#[cfg(feature = "async")]
+/// Reverts the applied migrations of the source with a version after the target in reverse version
+/// order, returns the names of the reverted migrations. This has the same semantics as
+/// [`rollback_migrations_with_options_async`][crate::rollback_migrations_with_options_async].
+pub async fn rollback_migrations_from_source_async(
+    client: &mut tokio_postgres::Client,
+    source: &dyn MigrationSource,
+    target: Option<u64>,
+    options: &MigrationOptions,
+) -> Result<Vec<String>, MigrationError> {
+    let targets = with_code_migrations(
+        source_targets(source)?,
+        options
+            .async_migrations
+            .iter()
+            .map(|migration| migration.name()),
+    )?;
+
+    lock::acquire_async(client, options).await?;
+    let result = rollback_targets_async(client, targets, target, options).await;
+    let released = lock::release_async(client, options).await;
+
+    let reverted = result?;
+    released?;
+
+    Ok(reverted)
+}
+
+/// Discovers the migration files of the source in version order, each followed by its down
+/// migration, for tools that inspect the migrations without running them.
+///
+/// Errors if the source fails to load, or the files are not valid migrations.
+pub fn discover_migrations_from_source(
+    source: &dyn MigrationSource,
+) -> Result<Vec<MigrationFile>, MigrationError> {
+    let targets = source_targets(source)?;
+
+    Ok(targets
+        .into_iter()
+        .flat_map(|target| {
+            let up = MigrationFile {
+                name: target.name,
+                path: target.path,
+                sql: target.sql,
+            };
+            core::iter::once(up).chain(target.down)
+        })
+        .collect())
+}
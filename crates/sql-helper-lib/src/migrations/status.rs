@@ -0,0 +1,227 @@
+//! Reporting the state of each migration without applying anything.
+//!
+
+use std::{collections::HashMap, path::PathBuf};
+
+use postgres::Row;
+
+use crate::migrations::{
+    DEFAULT_NAMESPACE, FilesystemSource, MigrationError, MigrationOptions, MigrationSource,
+    MigrationTarget, REPEATABLE_PREFIX, history, migration_version, source::source_targets,
+    with_code_migrations,
+};
+
+/// The state of a migration relative to a database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationState {
+    /// The migration has been applied and is unchanged.
+    Applied,
+    /// The migration has not been applied.
+    Pending,
+    /// The migration has been applied but its contents have changed since.
+    Modified,
+    /// The migration has been applied but its file, or registered code migration, no longer exists.
+    Missing,
+}
+impl core::fmt::Display for MigrationState {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match &self {
+            Self::Applied => write!(f, "applied"),
+            Self::Pending => write!(f, "pending"),
+            Self::Modified => write!(f, "modified"),
+            Self::Missing => write!(f, "missing"),
+        }
+    }
+}
+
+/// The state of a single migration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationStatus {
+    /// The name of the migration.
+    pub name: String,
+    /// The state of the migration.
+    pub state: MigrationState,
+}
+
+/// Reports the state of every migration in `current_dir()/migrations/*.sql` and every applied
+/// migration in the default namespace of the history, in version order. The database is not
+/// modified.
+///
+/// Errors if the migrations directory does not exist, instead of reporting every applied migration
+/// as missing.
+pub fn migration_status(
+    client: &mut postgres::Client,
+    migrations_directory: Option<PathBuf>,
+) -> Result<Vec<MigrationStatus>, MigrationError> {
+    migration_status_with_options(client, migrations_directory, &MigrationOptions::default())
+}
+
+/// Reports the state of every migration in `current_dir()/migrations/*.sql`, every code migration
+/// in the options, and every applied migration in the default namespace of the history, in
+/// version order. The database is not modified.
+///
+/// Errors if the migrations directory does not exist, instead of reporting every applied migration
+/// as missing.
+pub fn migration_status_with_options(
+    client: &mut postgres::Client,
+    migrations_directory: Option<PathBuf>,
+    options: &MigrationOptions,
+) -> Result<Vec<MigrationStatus>, MigrationError> {
+    let source = FilesystemSource {
+        directory: migrations_directory,
+        strict: true,
+    };
+
+    migration_status_from_source(client, &source, options)
+}
+
+/// Reports the state of every migration of the source, every code migration in the options, and
+/// every applied migration in the default namespace of the history, in version order. The database
+/// is not modified.
+pub fn migration_status_from_source(
+    client: &mut postgres::Client,
+    source: &dyn MigrationSource,
+    options: &MigrationOptions,
+) -> Result<Vec<MigrationStatus>, MigrationError> {
+    let targets = with_code_migrations(
+        source_targets(source)?,
+        options.migrations.iter().map(|migration| migration.name()),
+    )?;
+
+    let history_exists: bool = client
+        .query_one(history::HISTORY_EXISTS, &[])
+        .and_then(|row| row.try_get(0))
+        .map_err(|source| MigrationError::ReadHistory { source })?;
+    let applied = if history_exists {
+        client
+            .query(history::SELECT_APPLIED, &[])
+            .map_err(|source| MigrationError::ReadHistory { source })?
+    } else {
+        Vec::new()
+    };
+
+    statuses(targets, &applied)
+}
+
+#[cfg(feature = "async")]
+/// Reports the state of every migration in `current_dir()/migrations/*.sql` and every applied
+/// migration in the default namespace of the history, in version order. The database is not
+/// modified.
+///
+/// Errors if the migrations directory does not exist, instead of reporting every applied migration
+/// as missing.
+pub async fn migration_status_async(
+    client: &tokio_postgres::Client,
+    migrations_directory: Option<PathBuf>,
+) -> Result<Vec<MigrationStatus>, MigrationError> {
+    migration_status_with_options_async(client, migrations_directory, &MigrationOptions::default())
+        .await
+}
+
+#[cfg(feature = "async")]
+/// Reports the state of every migration in `current_dir()/migrations/*.sql`, every code migration
+/// in the options, and every applied migration in the default namespace of the history, in
+/// version order. The database is not modified.
+///
+/// Errors if the migrations directory does not exist, instead of reporting every applied migration
+/// as missing.
+pub async fn migration_status_with_options_async(
+    client: &tokio_postgres::Client,
+    migrations_directory: Option<PathBuf>,
+    options: &MigrationOptions,
+) -> Result<Vec<MigrationStatus>, MigrationError> {
+    let source = FilesystemSource {
+        directory: migrations_directory,
+        strict: true,
+    };
+
+    migration_status_from_source_async(client, &source, options).await
+}
+
+#[cfg(feature = "async")]
+/// Reports the state of every migration of the source, every code migration in the options, and
+/// every applied migration in the default namespace of the history, in version order. The database
+/// is not modified.
+pub async fn migration_status_from_source_async(
+    client: &tokio_postgres::Client,
+    source: &dyn MigrationSource,
+    options: &MigrationOptions,
+) -> Result<Vec<MigrationStatus>, MigrationError> {
+    let targets = with_code_migrations(
+        source_targets(source)?,
+        options
+            .async_migrations
+            .iter()
+            .map(|migration| migration.name()),
+    )?;
+
+    let history_exists: bool = client
+        .query_one(history::HISTORY_EXISTS, &[])
+        .await
+        .and_then(|row| row.try_get(0))
+        .map_err(|source| MigrationError::ReadHistory { source })?;
+    let applied = if history_exists {
+        client
+            .query(history::SELECT_APPLIED, &[])
+            .await
+            .map_err(|source| MigrationError::ReadHistory { source })?
+    } else {
+        Vec::new()
+    };
+
+    statuses(targets, &applied)
+}
+
+/// Compares the targets against the history.
+fn statuses(
+    targets: Vec<MigrationTarget>,
+    applied: &[Row],
+) -> Result<Vec<MigrationStatus>, MigrationError> {
+    let mut applied: HashMap<String, Vec<u8>> = history::applied_checksums(applied)?
+        .into_iter()
+        .filter(|((namespace, _), _)| namespace == DEFAULT_NAMESPACE)
+        .map(|((_, name), checksum)| (name, checksum))
+        .collect();
+
+    // Baselines are only used by a fresh database, otherwise they are only reported once applied.
+    let is_fresh = applied.is_empty();
+    let targets: Vec<MigrationTarget> = targets
+        .into_iter()
+        .filter(|target| !target.baseline || is_fresh || applied.contains_key(&target.name))
+        .collect();
+
+    let mut statuses: Vec<MigrationStatus> = targets
+        .into_iter()
+        .map(|target| {
+            let state = match applied.remove(&target.name) {
+                Some(checksum) if checksum == target.checksum => MigrationState::Applied,
+                // Changed repeatable migrations are re-applied by the next run.
+                Some(_) if target.repeatable => MigrationState::Pending,
+                Some(_) => MigrationState::Modified,
+                None => MigrationState::Pending,
+            };
+
+            MigrationStatus {
+                name: target.name,
+                state,
+            }
+        })
+        .collect();
+
+    // Applied code migrations that are no longer registered are missing like deleted files.
+    statuses.extend(applied.into_keys().map(|name| MigrationStatus {
+        name,
+        state: MigrationState::Missing,
+    }));
+    statuses.sort_by(|a, b| {
+        let order = |status: &MigrationStatus| {
+            (
+                status.name.starts_with(REPEATABLE_PREFIX),
+                migration_version(&status.name).ok(),
+            )
+        };
+        order(a).cmp(&order(b)).then_with(|| a.name.cmp(&b.name))
+    });
+
+    Ok(statuses)
+}
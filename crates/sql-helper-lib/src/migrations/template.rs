@@ -0,0 +1,111 @@
+//! Substitution of `${variable}` placeholders in migration files.
+//!
+
+use std::collections::HashMap;
+
+use crate::migrations::{MigrationError, MigrationTarget};
+
+/// Replaces the `${variable}` placeholders in the SQL of each target with the value of the
+/// variable.
+///
+/// Checksums are left as is, so they describe the template rather than the rendered SQL.
+pub fn render_targets(
+    mut targets: Vec<MigrationTarget>,
+    variables: &HashMap<String, String>,
+) -> Result<Vec<MigrationTarget>, MigrationError> {
+    for target in &mut targets {
+        if target.code.is_some() {
+            continue;
+        }
+
+        target.sql =
+            render(&target.sql, variables).map_err(|variable| MigrationError::MissingVariable {
+                name: target.name.clone(),
+                variable,
+            })?;
+    }
+
+    Ok(targets)
+}
+
+/// Replaces the `${variable}` placeholders in the SQL, returns the name of the first variable
+/// without a value on failure.
+///
+/// Only placeholders whose name consists of ASCII alphanumerics and underscores are replaced.
+fn render(sql: &str, variables: &HashMap<String, String>) -> Result<String, String> {
+    let mut rendered = String::with_capacity(sql.len());
+    let mut remaining = sql;
+
+    while let Some(start) = remaining.find("${") {
+        rendered.push_str(&remaining[..start]);
+        let after = &remaining[start + 2..];
+
+        let placeholder = after.find('}').map(|end| &after[..end]).filter(|variable| {
+            !variable.is_empty()
+                && variable
+                    .bytes()
+                    .all(|byte| byte.is_ascii_alphanumeric() || byte == b'_')
+        });
+
+        match placeholder {
+            Some(variable) => {
+                let value = variables
+                    .get(variable)
+                    .ok_or_else(|| variable.to_string())?;
+                rendered.push_str(value);
+                remaining = &after[variable.len() + 1..];
+            }
+            None => {
+                rendered.push_str("${");
+                remaining = after;
+            }
+        }
+    }
+    rendered.push_str(remaining);
+
+    Ok(rendered)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::render;
+
+    fn variables() -> HashMap<String, String> {
+        HashMap::from([
+            ("schema".to_string(), "tenant_1".to_string()),
+            ("role".to_string(), "app".to_string()),
+        ])
+    }
+
+    #[test]
+    fn replaces_placeholders() {
+        assert_eq!(
+            render("GRANT USAGE ON SCHEMA ${schema} TO ${role};", &variables()).unwrap(),
+            "GRANT USAGE ON SCHEMA tenant_1 TO app;"
+        );
+    }
+
+    #[test]
+    fn errors_on_missing_variable() {
+        assert_eq!(
+            render("CREATE SCHEMA ${missing};", &variables()),
+            Err("missing".to_string())
+        );
+    }
+
+    #[test]
+    fn passes_through_invalid_placeholders() {
+        let sql = "SELECT '${invalid name}', '${}', '${schema', '$schema';";
+        assert_eq!(render(sql, &variables()).unwrap(), sql);
+    }
+
+    #[test]
+    fn keeps_multibyte_characters() {
+        assert_eq!(
+            render("SELECT 'é${schema}ü', '${ü}';", &variables()).unwrap(),
+            "SELECT 'étenant_1ü', '${ü}';"
+        );
+    }
+}
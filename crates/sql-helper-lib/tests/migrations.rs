@@ -0,0 +1,200 @@
+#![allow(missing_docs)]
+
+use core::time::Duration;
+use std::sync::Arc;
+
+use ts_sql_helper_lib::{
+    CodeMigrationError, DEFAULT_LOCK_KEY, InMemorySource, Migration, MigrationError,
+    MigrationOptions, MigrationState, migration_status_from_source, perform_migrations_from_source,
+    repair_migrations_from_source, rollback_migrations_from_source, test::get_test_database,
+};
+
+#[test]
+fn second_run_applies_nothing() {
+    let (mut client, _container) = get_test_database();
+
+    let source = InMemorySource::new()
+        .with_migration(
+            "1_create_users.sql",
+            "CREATE TABLE users (id INT4 PRIMARY KEY);",
+        )
+        .with_migration(
+            "2_add_name.sql",
+            "ALTER TABLE users ADD COLUMN name VARCHAR;",
+        );
+
+    let applied =
+        perform_migrations_from_source(&mut client, &source, &MigrationOptions::default()).unwrap();
+    assert_eq!(applied, ["1_create_users.sql", "2_add_name.sql"]);
+
+    let applied =
+        perform_migrations_from_source(&mut client, &source, &MigrationOptions::default()).unwrap();
+    assert!(applied.is_empty());
+
+    let history = client
+        .query_one("SELECT COUNT(*) FROM _sql_helper_migrations;", &[])
+        .unwrap();
+    assert_eq!(history.get::<_, i64>(0), 2);
+}
+
+#[test]
+fn rollback_from_source_reverts_migrations() {
+    let (mut client, _container) = get_test_database();
+
+    let source = InMemorySource::new()
+        .with_migration(
+            "1_create_users.sql",
+            "CREATE TABLE users (id INT4 PRIMARY KEY);",
+        )
+        .with_migration(
+            "2_add_name.up.sql",
+            "ALTER TABLE users ADD COLUMN name VARCHAR;",
+        )
+        .with_migration("2_add_name.down.sql", "ALTER TABLE users DROP COLUMN name;");
+    let options = MigrationOptions::default();
+
+    perform_migrations_from_source(&mut client, &source, &options).unwrap();
+
+    let reverted =
+        rollback_migrations_from_source(&mut client, &source, Some(1), &options).unwrap();
+    assert_eq!(reverted.len(), 1);
+
+    let states: Vec<MigrationState> = migration_status_from_source(&mut client, &source, &options)
+        .unwrap()
+        .into_iter()
+        .map(|status| status.state)
+        .collect();
+    assert_eq!(states, [MigrationState::Applied, MigrationState::Pending]);
+
+    let name_exists: bool = client
+        .query_one(
+            "SELECT EXISTS (SELECT 1 FROM information_schema.columns \
+            WHERE table_name = 'users' AND column_name = 'name');",
+            &[],
+        )
+        .unwrap()
+        .get(0);
+    assert!(!name_exists);
+}
+
+#[test]
+fn repair_accepts_modified_migrations() {
+    let (mut client, _container) = get_test_database();
+    let options = MigrationOptions::default();
+
+    let source = InMemorySource::new().with_migration(
+        "1_create_users.sql",
+        "CREATE TABLE users (id INT4 PRIMARY KEY);",
+    );
+    perform_migrations_from_source(&mut client, &source, &options).unwrap();
+
+    let modified = InMemorySource::new().with_migration(
+        "1_create_users.sql",
+        "CREATE TABLE users (id INT8 PRIMARY KEY);",
+    );
+    let error = perform_migrations_from_source(&mut client, &modified, &options).unwrap_err();
+    assert!(
+        matches!(&error, MigrationError::ChecksumMismatch { name, .. } if name == "1_create_users.sql"),
+        "unexpected error: {error}"
+    );
+
+    let repaired = repair_migrations_from_source(&mut client, &modified, &options).unwrap();
+    assert_eq!(repaired, ["1_create_users.sql"]);
+
+    let applied = perform_migrations_from_source(&mut client, &modified, &options).unwrap();
+    assert!(applied.is_empty());
+}
+
+#[test]
+fn lock_timeout_stops_waiting_for_lock() {
+    let (mut client, container) = get_test_database();
+
+    let ip = container.get_host().unwrap();
+    let port = container.get_host_port_ipv4(5432).unwrap();
+    let connection_string = format!("postgres://postgres:postgres@{ip}:{port}/postgres");
+    let mut holder = postgres::Client::connect(&connection_string, postgres::NoTls).unwrap();
+    holder
+        .execute("SELECT pg_advisory_lock($1::INT8);", &[&DEFAULT_LOCK_KEY])
+        .unwrap();
+
+    let source = InMemorySource::new().with_migration(
+        "1_create_users.sql",
+        "CREATE TABLE users (id INT4 PRIMARY KEY);",
+    );
+    let options = MigrationOptions {
+        lock_timeout: Some(Duration::from_millis(100)),
+        ..Default::default()
+    };
+
+    let error = perform_migrations_from_source(&mut client, &source, &options).unwrap_err();
+    assert!(
+        matches!(error, MigrationError::AcquireLock { .. }),
+        "unexpected error: {error}"
+    );
+
+    holder
+        .execute("SELECT pg_advisory_unlock($1::INT8);", &[&DEFAULT_LOCK_KEY])
+        .unwrap();
+
+    let applied = perform_migrations_from_source(&mut client, &source, &options).unwrap();
+    assert_eq!(applied, ["1_create_users.sql"]);
+}
+
+/// Adds an email column to the users table.
+struct AddEmail;
+impl Migration for AddEmail {
+    fn name(&self) -> &str {
+        "2_add_email"
+    }
+
+    fn up(&self, transaction: &mut postgres::Transaction<'_>) -> Result<(), CodeMigrationError> {
+        transaction.batch_execute("ALTER TABLE users ADD COLUMN email VARCHAR;")?;
+        Ok(())
+    }
+}
+
+#[test]
+fn status_reports_code_migrations() {
+    let (mut client, _container) = get_test_database();
+
+    let source = InMemorySource::new().with_migration(
+        "1_create_users.sql",
+        "CREATE TABLE users (id INT4 PRIMARY KEY);",
+    );
+    let options = MigrationOptions {
+        migrations: vec![Arc::new(AddEmail)],
+        ..Default::default()
+    };
+    let states = |client: &mut postgres::Client, options: &MigrationOptions| {
+        migration_status_from_source(client, &source, options)
+            .unwrap()
+            .into_iter()
+            .map(|status| (status.name, status.state))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        states(&mut client, &options),
+        [
+            ("1_create_users.sql".to_string(), MigrationState::Pending),
+            ("2_add_email".to_string(), MigrationState::Pending),
+        ]
+    );
+
+    perform_migrations_from_source(&mut client, &source, &options).unwrap();
+    assert_eq!(
+        states(&mut client, &options),
+        [
+            ("1_create_users.sql".to_string(), MigrationState::Applied),
+            ("2_add_email".to_string(), MigrationState::Applied),
+        ]
+    );
+
+    assert_eq!(
+        states(&mut client, &MigrationOptions::default()),
+        [
+            ("1_create_users.sql".to_string(), MigrationState::Applied),
+            ("2_add_email".to_string(), MigrationState::Missing),
+        ]
+    );
+}
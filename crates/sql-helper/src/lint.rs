@@ -0,0 +1,404 @@
+//! Checks for migration patterns that are dangerous to deploy against a live database.
+//!
+//! Statements are found by splitting on `;` outside of comments, quoted identifiers, string
+//! literals, and dollar quoted bodies. The contents of literals and bodies are not checked.
+//!
+
+/// A dangerous pattern found in a migration.
+pub struct Finding {
+    /// The line the statement starts on, starting from 1.
+    pub line: usize,
+    /// What was found and why it is dangerous.
+    pub message: &'static str,
+}
+
+/// Checks each statement of the migration for dangerous patterns.
+pub fn lint_migration(sql: &str) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for (start, statement) in statements(sql) {
+        let line = sql[..start].matches('\n').count() + 1;
+        findings.extend(
+            lint_statement(&statement)
+                .into_iter()
+                .map(|message| Finding { line, message }),
+        );
+    }
+
+    findings
+}
+
+/// Splits the migration into its statements, returns the byte offset each statement starts at and
+/// its code. Comments are replaced by a space, and string literals and dollar quoted bodies by an
+/// empty string literal.
+fn statements(sql: &str) -> Vec<(usize, String)> {
+    let mut statements = Vec::new();
+    let mut statement = String::new();
+    let mut start = None;
+
+    let mut index = 0;
+    while let Some(character) = sql[index..].chars().next() {
+        let rest = &sql[index..];
+
+        let (length, code) = if rest.starts_with("--") {
+            (rest.find('\n').unwrap_or(rest.len()), " ")
+        } else if let Some(body) = rest.strip_prefix("/*") {
+            (body.find("*/").map_or(rest.len(), |end| end + 4), " ")
+        } else if character == '\'' || character == '"' {
+            let length = rest[1..].find(character).map_or(rest.len(), |end| end + 2);
+            // Quoted identifiers are code, only the contents of literals are skipped.
+            let code = if character == '"' {
+                &rest[..length]
+            } else {
+                "''"
+            };
+            (length, code)
+        } else if let Some(tag) = dollar_quote_tag(rest) {
+            let body = &rest[tag.len()..];
+            (
+                body.find(tag).map_or(rest.len(), |end| end + 2 * tag.len()),
+                "''",
+            )
+        } else if character == ';' {
+            let code = core::mem::take(&mut statement);
+            if let Some(start) = start.take() {
+                statements.push((start, code));
+            }
+            index += 1;
+            continue;
+        } else {
+            (character.len_utf8(), &rest[..character.len_utf8()])
+        };
+
+        if start.is_none() && !code.trim().is_empty() {
+            start = Some(index);
+        }
+        statement.push_str(code);
+        index += length;
+    }
+
+    if let Some(start) = start {
+        statements.push((start, statement));
+    }
+
+    statements
+}
+
+/// The `$tag$` that opens a dollar quoted body at the start of the SQL, if any.
+fn dollar_quote_tag(sql: &str) -> Option<&str> {
+    let tag = sql.strip_prefix('$')?;
+    let length = tag.find(|character: char| !(character.is_alphanumeric() || character == '_'))?;
+
+    let is_tag = tag[length..].starts_with('$')
+        && !tag.starts_with(|character: char| character.is_ascii_digit());
+
+    is_tag.then(|| &sql[..length + 2])
+}
+
+/// Checks a single statement for dangerous patterns.
+fn lint_statement(statement: &str) -> Vec<&'static str> {
+    let statement = statement.to_uppercase();
+    let statement_words = words(&statement);
+    let mut messages = Vec::new();
+
+    match statement_words.as_slice() {
+        ["CREATE", "INDEX" | "UNIQUE", ..] => {
+            if !statement_words.contains(&"CONCURRENTLY") {
+                messages.push("`CREATE INDEX` without `CONCURRENTLY` blocks writes to the table");
+            }
+            if !has_if_not_exists(&statement_words) {
+                messages.push("`CREATE INDEX` without `IF NOT EXISTS` cannot be re-run");
+            }
+        }
+
+        ["CREATE", "TABLE" | "SCHEMA" | "SEQUENCE" | "EXTENSION", ..]
+            if !has_if_not_exists(&statement_words) =>
+        {
+            messages.push("`CREATE` without `IF NOT EXISTS` cannot be re-run");
+        }
+
+        ["ALTER", "TABLE", ..] => {
+            for clause in clauses(&statement) {
+                messages.extend(lint_alter_table_clause(&words(&clause)));
+            }
+        }
+
+        _ => {}
+    }
+
+    messages
+}
+
+/// Checks a single clause of an `ALTER TABLE` statement, the first clause includes the
+/// `ALTER TABLE <name>` prefix.
+fn lint_alter_table_clause(words: &[&str]) -> Vec<&'static str> {
+    let mut messages = Vec::new();
+
+    let action = match words {
+        ["ALTER", "TABLE", "IF", "EXISTS", "ONLY", _, action @ ..]
+        | ["ALTER", "TABLE", "IF", "EXISTS", _, action @ ..]
+        | ["ALTER", "TABLE", "ONLY", _, action @ ..]
+        | ["ALTER", "TABLE", _, action @ ..] => action,
+        action => action,
+    };
+
+    match action {
+        [
+            "ADD",
+            "CONSTRAINT" | "PRIMARY" | "UNIQUE" | "FOREIGN" | "CHECK" | "EXCLUDE",
+            ..,
+        ] => {}
+
+        ["ADD", ..] => {
+            let not_null = action.windows(2).any(|pair| pair == ["NOT", "NULL"]);
+            if not_null && !action.contains(&"DEFAULT") {
+                messages.push(
+                    "adding a `NOT NULL` column without a `DEFAULT` fails on tables with rows",
+                );
+            }
+            if !has_if_not_exists(action) {
+                messages.push("`ADD COLUMN` without `IF NOT EXISTS` cannot be re-run");
+            }
+        }
+
+        ["DROP", "CONSTRAINT", ..] => {}
+
+        ["DROP", ..] => {
+            messages.push("dropping a column breaks code that still reads it");
+        }
+
+        ["ALTER", "COLUMN", _, rest @ ..] | ["ALTER", _, rest @ ..] => {
+            if matches!(rest, ["TYPE", ..] | ["SET", "DATA", "TYPE", ..]) {
+                messages
+                    .push("changing a column's type rewrites the table under an exclusive lock");
+            }
+        }
+
+        _ => {}
+    }
+
+    messages
+}
+
+/// If the words contain `IF NOT EXISTS`.
+fn has_if_not_exists(words: &[&str]) -> bool {
+    words
+        .windows(3)
+        .any(|window| window == ["IF", "NOT", "EXISTS"])
+}
+
+/// Splits a statement into the clauses separated by commas outside of parentheses.
+fn clauses(statement: &str) -> Vec<String> {
+    let mut clauses = vec![String::new()];
+    let mut depth = 0_usize;
+    for character in statement.chars() {
+        match character {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                clauses.push(String::new());
+                continue;
+            }
+            _ => {}
+        }
+        if let Some(clause) = clauses.last_mut() {
+            clause.push(character);
+        }
+    }
+
+    clauses
+}
+
+/// Splits a statement into its words, treating punctuation as whitespace.
+fn words(statement: &str) -> Vec<&str> {
+    statement
+        .split(|character: char| character.is_whitespace() || "(),".contains(character))
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::lint_migration;
+
+    /// The line and message of each finding.
+    fn findings(sql: &str) -> Vec<(usize, &'static str)> {
+        lint_migration(sql)
+            .into_iter()
+            .map(|finding| (finding.line, finding.message))
+            .collect()
+    }
+
+    #[test]
+    fn flags_create_index() {
+        assert_eq!(
+            findings("CREATE INDEX users_name ON users (name);"),
+            vec![
+                (
+                    1,
+                    "`CREATE INDEX` without `CONCURRENTLY` blocks writes to the table"
+                ),
+                (1, "`CREATE INDEX` without `IF NOT EXISTS` cannot be re-run"),
+            ]
+        );
+        assert_eq!(
+            findings("CREATE UNIQUE INDEX IF NOT EXISTS users_name ON users (name);"),
+            vec![(
+                1,
+                "`CREATE INDEX` without `CONCURRENTLY` blocks writes to the table"
+            )]
+        );
+        assert!(
+            findings("CREATE INDEX CONCURRENTLY IF NOT EXISTS users_name ON users (name);")
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn flags_create_without_if_not_exists() {
+        assert_eq!(
+            findings("CREATE TABLE users (id INT4);"),
+            vec![(1, "`CREATE` without `IF NOT EXISTS` cannot be re-run")]
+        );
+        assert_eq!(
+            findings("create extension pgcrypto;"),
+            vec![(1, "`CREATE` without `IF NOT EXISTS` cannot be re-run")]
+        );
+        assert!(findings("CREATE TABLE IF NOT EXISTS users (id INT4);").is_empty());
+    }
+
+    #[test]
+    fn flags_not_null_column_without_default() {
+        assert_eq!(
+            findings("ALTER TABLE users ADD COLUMN IF NOT EXISTS name TEXT NOT NULL;"),
+            vec![(
+                1,
+                "adding a `NOT NULL` column without a `DEFAULT` fails on tables with rows"
+            )]
+        );
+        assert!(
+            findings("ALTER TABLE users ADD COLUMN IF NOT EXISTS name TEXT NOT NULL DEFAULT '';")
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn flags_add_column_without_if_not_exists() {
+        assert_eq!(
+            findings("ALTER TABLE IF EXISTS ONLY users ADD COLUMN name TEXT;"),
+            vec![(1, "`ADD COLUMN` without `IF NOT EXISTS` cannot be re-run")]
+        );
+    }
+
+    #[test]
+    fn flags_drop_column() {
+        assert_eq!(
+            findings("ALTER TABLE users DROP COLUMN name;"),
+            vec![(1, "dropping a column breaks code that still reads it")]
+        );
+    }
+
+    #[test]
+    fn flags_column_type_change() {
+        let message = "changing a column's type rewrites the table under an exclusive lock";
+        assert_eq!(
+            findings("ALTER TABLE users ALTER COLUMN name TYPE VARCHAR(64);"),
+            vec![(1, message)]
+        );
+        assert_eq!(
+            findings("ALTER TABLE users ALTER name SET DATA TYPE VARCHAR(64);"),
+            vec![(1, message)]
+        );
+        assert!(findings("ALTER TABLE users ALTER COLUMN name SET NOT NULL;").is_empty());
+    }
+
+    #[test]
+    fn ignores_constraints() {
+        assert!(
+            findings(
+                "ALTER TABLE users ADD CONSTRAINT users_name UNIQUE (name), \
+                DROP CONSTRAINT users_email;"
+            )
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn checks_each_alter_table_clause() {
+        assert_eq!(
+            findings(
+                "ALTER TABLE users ADD COLUMN IF NOT EXISTS name TEXT, DROP COLUMN email, \
+                ADD COLUMN IF NOT EXISTS age NUMERIC(3, 0) DEFAULT 0 NOT NULL;"
+            ),
+            vec![(1, "dropping a column breaks code that still reads it")]
+        );
+    }
+
+    #[test]
+    fn reports_statement_line() {
+        let sql = "-- Users; and their names.\nCREATE TABLE IF NOT EXISTS users (id INT4);\n\n\
+            ALTER TABLE users\n    DROP COLUMN name;\n";
+
+        assert_eq!(
+            findings(sql),
+            vec![(4, "dropping a column breaks code that still reads it")]
+        );
+    }
+
+    #[test]
+    fn skips_semicolons_in_literals_and_bodies() {
+        assert!(
+            findings("INSERT INTO log (message) VALUES ('step one; CREATE TABLE done');")
+                .is_empty()
+        );
+        assert!(
+            findings(
+                "CREATE FUNCTION noop() RETURNS VOID AS $$ BEGIN; CREATE TABLE t (id INT4); END $$ \
+                LANGUAGE plpgsql;"
+            )
+            .is_empty()
+        );
+        assert!(
+            findings(
+                "CREATE FUNCTION noop() RETURNS VOID AS $body$ SELECT ';$$'; $body$ LANGUAGE sql;\n\
+                ALTER TABLE \"odd;name\" ADD COLUMN IF NOT EXISTS note TEXT;"
+            )
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn skips_comment_markers_in_literals() {
+        let sql = "ALTER TABLE users ADD COLUMN IF NOT EXISTS note TEXT CHECK (note <> '--');\n\
+            CREATE INDEX users_name ON users (name);\n";
+
+        assert_eq!(
+            findings(sql),
+            vec![
+                (
+                    2,
+                    "`CREATE INDEX` without `CONCURRENTLY` blocks writes to the table"
+                ),
+                (2, "`CREATE INDEX` without `IF NOT EXISTS` cannot be re-run"),
+            ]
+        );
+    }
+
+    #[test]
+    fn ignores_literal_contents() {
+        assert!(
+            findings("ALTER TABLE users ADD COLUMN IF NOT EXISTS note TEXT DEFAULT 'NOT NULL';")
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn skips_comments() {
+        let sql = "/* CREATE TABLE users (id INT4);\n*/ -- DROP COLUMN;\n\
+            /* ; */ ALTER TABLE users DROP COLUMN name;\n";
+
+        assert_eq!(
+            findings(sql),
+            vec![(3, "dropping a column breaks code that still reads it")]
+        );
+    }
+}
@@ -2,16 +2,25 @@
 //! Split helper CLI for working with SQL.
 
 use std::{
+    env::current_dir,
+    fs,
     io::{self, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
+    process::{self, Command},
+    sync::Arc,
 };
 
 use clap::{Parser, Subcommand};
-use testcontainers::{ImageExt, runners::SyncRunner};
+use testcontainers::{Container, ImageExt, core::ExecCommand, runners::SyncRunner};
 use testcontainers_modules::postgres::Postgres;
 use ts_cli_helper::{Action, ActionResult, print_success};
-use ts_rust_helper::error::ReportProgramExit;
-use ts_sql_helper_lib::perform_migrations;
+use ts_rust_helper::error::{ProgramReport, ReportProgramExit};
+use ts_sql_helper_lib::{
+    BASELINE_SUFFIX, DOWN_SUFFIX, MigrationObserver, MigrationOptions, MigrationReport,
+    discover_migrations, migration_status, migration_version, perform_migrations_with_options,
+};
+
+mod lint;
 
 #[derive(Debug, Parser)]
 #[command(name = "sql-helper")]
@@ -32,6 +41,58 @@ pub enum Commands {
         #[arg(short, long)]
         migrations: Option<PathBuf>,
     },
+
+    /// Prints the state of each migration against a database without applying anything.
+    MigrationStatus {
+        /// Connection string of the database to compare against.
+        #[arg(short, long)]
+        database_url: String,
+
+        /// Path to a directory containing the migrations.
+        #[arg(short, long)]
+        migrations: Option<PathBuf>,
+    },
+
+    /// Applies the migrations to a database container and writes the resulting schema as a
+    /// baseline that replaces the migrations on a fresh database.
+    ///
+    /// The baseline only contains the schema, so rows inserted by the replaced migrations are not
+    /// included, and replaced code migrations are recorded as applied without running. Keep such
+    /// changes in migrations after the baseline.
+    Squash {
+        /// Path to a directory containing the migrations.
+        #[arg(short, long)]
+        migrations: Option<PathBuf>,
+
+        /// Directory to write the baseline to, defaults to the migrations directory.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Checks the migrations for patterns that are dangerous to deploy against a live database,
+    /// exits with a failure if any are found.
+    LintMigrations {
+        /// Path to a directory containing the migrations.
+        #[arg(short, long)]
+        migrations: Option<PathBuf>,
+    },
+
+    /// Prepares every `query!` in the workspace against a database and writes their metadata to
+    /// `.sql-helper/`, so crates with the `verify` feature can check their queries without a
+    /// database.
+    Prepare {
+        /// Path to a directory containing migrations to set up the database container.
+        #[arg(short, long)]
+        migrations: Option<PathBuf>,
+
+        /// Connection string of an existing database to prepare against instead of a container.
+        #[arg(short, long)]
+        database_url: Option<String>,
+
+        /// Additional arguments for `cargo check`, such as the features to enable.
+        #[arg(last = true)]
+        cargo_args: Vec<String>,
+    },
 }
 
 fn main() -> ReportProgramExit {
@@ -39,26 +100,7 @@ fn main() -> ReportProgramExit {
 
     match cli.command {
         Commands::StartDatabase { migrations } => {
-            let mut action = Action::new("Starting", "Started", "database container", 0);
-
-            let container = Postgres::default()
-                .with_tag("17-alpine")
-                .start()
-                .bind_error(&mut action)?;
-            let ip = container.get_host().bind_error(&mut action)?;
-            let port = container.get_host_port_ipv4(5432).bind_result(action)?;
-
-            let connection_string = format!("postgres://postgres:postgres@{ip}:{port}/postgres");
-
-            // Perform migrations
-            {
-                let action = Action::new("Connecting", "Connected", "to database", 0);
-                let mut client = postgres::Client::connect(&connection_string, postgres::NoTls)
-                    .bind_result(action)?;
-
-                let action = Action::new("Running", "Ran", "migrations", 0);
-                perform_migrations(&mut client, migrations).bind_result(action)?;
-            }
+            let (_container, connection_string, _applied) = start_database(migrations)?;
 
             print_success(format!("Database available at `{connection_string}`"));
 
@@ -71,7 +113,308 @@ fn main() -> ReportProgramExit {
                 let _ = io::stdin().read_line(&mut buffer);
             }
         }
+
+        Commands::MigrationStatus {
+            database_url,
+            migrations,
+        } => {
+            let action = Action::new("Connecting", "Connected", "to database", 0);
+            let mut client =
+                postgres::Client::connect(&database_url, postgres::NoTls).bind_result(action)?;
+
+            let action = Action::new("Reading", "Read", "migration status", 0);
+            let statuses = migration_status(&mut client, migrations).bind_result(action)?;
+
+            let mut stdout = io::stdout().lock();
+            for status in statuses {
+                writeln!(stdout, "{:<8} {}", status.state.to_string(), status.name)?;
+            }
+            stdout.flush()?;
+        }
+
+        Commands::Squash { migrations, output } => {
+            let (container, _connection_string, applied) = start_database(migrations.clone())?;
+
+            let Some(version) = applied
+                .iter()
+                .filter_map(|name| migration_version(name).ok())
+                .max()
+            else {
+                writeln!(io::stderr(), "No migrations to squash")?;
+                return Ok(());
+            };
+
+            let mut action = Action::new("Dumping", "Dumped", "schema", 0);
+            let mut result = container
+                .exec(ExecCommand::new([
+                    "pg_dump",
+                    "--schema-only",
+                    "--no-owner",
+                    "--no-privileges",
+                    "--exclude-table=_sql_helper_migrations",
+                    "--username=postgres",
+                    "postgres",
+                ]))
+                .bind_error(&mut action)?;
+            let dump = result.stdout_to_vec().bind_result(action)?;
+            let baseline = baseline_from_dump(&String::from_utf8_lossy(&dump));
+
+            let directory = match output.or(migrations) {
+                Some(directory) => directory,
+                None => current_dir()?.join("migrations"),
+            };
+            let path = directory.join(format!("{version}_baseline{BASELINE_SUFFIX}"));
+            fs::write(&path, baseline)?;
+
+            print_success(format!("Baseline written to `{}`", path.display()));
+        }
+
+        Commands::LintMigrations { migrations } => {
+            let action = Action::new("Reading", "Read", "migrations", 0);
+            let files = discover_migrations(migrations).bind_result(action)?;
+            // Baselines are generated by `pg_dump` and describe a schema rather than a change to one,
+            // down migrations are expected to drop what their up migration added.
+            let files: Vec<_> = files
+                .into_iter()
+                .filter(|file| {
+                    !file.name.ends_with(BASELINE_SUFFIX) && !file.name.ends_with(DOWN_SUFFIX)
+                })
+                .collect();
+
+            let mut finding_count = 0;
+            let mut stdout = io::stdout().lock();
+            for file in &files {
+                let location = file
+                    .path
+                    .as_ref()
+                    .map_or_else(|| file.name.clone(), |path| path.display().to_string());
+
+                for finding in lint::lint_migration(&file.sql) {
+                    finding_count += 1;
+                    writeln!(stdout, "{location}:{}: {}", finding.line, finding.message)?;
+                }
+            }
+            stdout.flush()?;
+
+            if finding_count > 0 {
+                writeln!(
+                    io::stderr(),
+                    "\nFound {finding_count} dangerous migration patterns"
+                )?;
+                process::exit(1);
+            }
+
+            print_success(format!(
+                "Checked {} migrations, no dangerous patterns found",
+                files.len()
+            ));
+        }
+
+        Commands::Prepare {
+            migrations,
+            database_url,
+            cargo_args,
+        } => {
+            // The container must outlive `cargo check`, and is dropped before exiting on failure as
+            // `process::exit` skips destructors, which would leave the container running.
+            let (container, database_url) = match database_url {
+                Some(database_url) => (None, database_url),
+                None => {
+                    let (container, connection_string, _applied) = start_database(migrations)?;
+                    (Some(container), connection_string)
+                }
+            };
+
+            let action = Action::new("Locating", "Located", "workspace", 0);
+            let output = Command::new("cargo")
+                .args(["locate-project", "--workspace", "--message-format", "plain"])
+                .output()
+                .bind_result(action)?;
+            if !output.status.success() {
+                io::stderr().write_all(&output.stderr)?;
+                drop(container);
+                process::exit(1);
+            }
+            let manifest = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+            let workspace = manifest.parent().unwrap_or(Path::new("."));
+
+            // A separate target directory that is cleared on each run, so every `query!` is
+            // expanded again. The metadata only replaces the cache once every query was prepared.
+            let target_directory = workspace.join("target").join("sql-helper-prepare");
+            let prepared_directory = target_directory.join(CACHE_DIRECTORY);
+            if target_directory.exists() {
+                fs::remove_dir_all(&target_directory)?;
+            }
+            fs::create_dir_all(&prepared_directory)?;
+
+            let status = Command::new("cargo")
+                .args(["check", "--workspace", "--all-targets"])
+                .args(&cargo_args)
+                .current_dir(workspace)
+                .env("DATABASE_URL", &database_url)
+                .env("SQL_HELPER_PREPARE", &prepared_directory)
+                .env("CARGO_TARGET_DIR", &target_directory)
+                .status()?;
+            if !status.success() {
+                writeln!(io::stderr(), "\nFailed to prepare the queries")?;
+                drop(container);
+                process::exit(1);
+            }
+
+            let cache_directory = workspace.join(CACHE_DIRECTORY);
+            if cache_directory.exists() {
+                fs::remove_dir_all(&cache_directory)?;
+            }
+            fs::rename(&prepared_directory, &cache_directory)?;
+
+            print_success(format!(
+                "Prepared {} queries into `{}`",
+                count_files(&cache_directory)?,
+                cache_directory.display()
+            ));
+        }
     }
 
     Ok(())
 }
+
+/// Starts a database container and runs the migrations on it, returns the container, its
+/// connection string, and the names of the applied migrations. The database is removed when the
+/// container is dropped.
+fn start_database(
+    migrations: Option<PathBuf>,
+) -> Result<(Container<Postgres>, String, Vec<String>), ProgramReport> {
+    let mut action = Action::new("Starting", "Started", "database container", 0);
+
+    let container = Postgres::default()
+        .with_tag("17-alpine")
+        .start()
+        .bind_error(&mut action)?;
+    let ip = container.get_host().bind_error(&mut action)?;
+    let port = container.get_host_port_ipv4(5432).bind_result(action)?;
+
+    let connection_string = format!("postgres://postgres:postgres@{ip}:{port}/postgres");
+
+    let action = Action::new("Connecting", "Connected", "to database", 0);
+    let mut client =
+        postgres::Client::connect(&connection_string, postgres::NoTls).bind_result(action)?;
+
+    let action = Action::new("Running", "Ran", "migrations", 0);
+    let applied = perform_migrations_with_options(&mut client, migrations, &migration_options())
+        .bind_result(action)?;
+
+    Ok((container, connection_string, applied))
+}
+
+/// The directory the query metadata is cached in, relative to the workspace root.
+const CACHE_DIRECTORY: &str = ".sql-helper";
+
+/// Counts the files in a directory and its subdirectories.
+fn count_files(directory: &Path) -> io::Result<usize> {
+    let mut count = 0;
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            count += count_files(&entry.path())?;
+        } else {
+            count += 1;
+        }
+    }
+
+    Ok(count)
+}
+
+/// Prints each migration as it is applied.
+struct PrintObserver;
+impl MigrationObserver for PrintObserver {
+    fn after_migration(&self, report: &MigrationReport) {
+        let rows = report
+            .rows_affected
+            .map(|rows| format!(", {rows} rows affected"))
+            .unwrap_or_default();
+        print_success(format!(
+            "Applied `{}` in {:.2?}{rows}",
+            report.name, report.duration
+        ));
+    }
+}
+
+/// The options for running migrations from the CLI.
+fn migration_options() -> MigrationOptions {
+    MigrationOptions {
+        observer: Some(Arc::new(PrintObserver)),
+        ..Default::default()
+    }
+}
+
+/// Removes the parts of a `pg_dump` script that cannot run through the migration runner, the
+/// `psql` meta-commands, and the `search_path` reset and top level `SET` statements that would
+/// change the settings of the session running the migrations.
+fn baseline_from_dump(dump: &str) -> String {
+    dump.lines()
+        .filter(|line| {
+            !line.starts_with('\\')
+                && !line.starts_with("SET ")
+                && !line.starts_with("SELECT pg_catalog.set_config('search_path'")
+        })
+        .fold(String::new(), |mut baseline, line| {
+            baseline.push_str(line);
+            baseline.push('\n');
+            baseline
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::baseline_from_dump;
+
+    #[test]
+    fn removes_session_settings_and_meta_commands() {
+        let dump = "\
+--
+-- PostgreSQL database dump
+--
+
+\\restrict abc123
+SET statement_timeout = 0;
+SET client_encoding = 'UTF8';
+SELECT pg_catalog.set_config('search_path', '', false);
+
+CREATE TABLE public.users (
+    id integer NOT NULL,
+    name character varying DEFAULT 'SET '::character varying
+);
+
+ALTER TABLE ONLY public.users
+    ADD CONSTRAINT users_pkey PRIMARY KEY (id);
+
+\\unrestrict abc123
+";
+
+        assert_eq!(
+            baseline_from_dump(dump),
+            "\
+--
+-- PostgreSQL database dump
+--
+
+
+CREATE TABLE public.users (
+    id integer NOT NULL,
+    name character varying DEFAULT 'SET '::character varying
+);
+
+ALTER TABLE ONLY public.users
+    ADD CONSTRAINT users_pkey PRIMARY KEY (id);
+
+"
+        );
+    }
+
+    #[test]
+    fn keeps_indented_set_clauses() {
+        let dump = "UPDATE users\n    SET name = 'unknown';\n";
+
+        assert_eq!(baseline_from_dump(dump), dump);
+    }
+}